@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+use std::{hint::black_box, net::Ipv4Addr};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ip_geo::{
+    country_list::{all_countries, Country},
+    IpAddrMap,
+};
+
+/// The number of entries in the benchmarked map, about the size of the Tor IPv4 database.
+const ENTRIES: u32 = 200_000;
+/// The number of addresses searched per iteration.
+const QUERIES: usize = 1_000;
+/// The number of addresses in each sorted batch.
+const BATCH: usize = 100_000;
+
+/// Build a map of `ENTRIES` evenly spaced ranges across the IPv4 address space, cycling through
+/// every country.
+///
+/// Each range covers three quarters of its slot, so that some searches find no value, as with a
+/// real database.
+fn build_map() -> IpAddrMap<Ipv4Addr, Country> {
+    let slot = u32::MAX / ENTRIES;
+    let countries: Vec<&Country> = all_countries().collect();
+
+    let rows = (0..ENTRIES)
+        .zip(countries.into_iter().cycle())
+        .map(|(index, country)| {
+            let start = index * slot;
+            let end = start + slot / 4 * 3;
+
+            (
+                Ipv4Addr::from_bits(start),
+                Ipv4Addr::from_bits(end),
+                country.clone(),
+            )
+        });
+
+    IpAddrMap::from_rows(rows).expect("non-empty ranges")
+}
+
+/// Build a layered map of `ENTRIES` ranges, as with a city-level database: like `build_map`, but
+/// every eighth slot is a country range covering the next eight slots, which hold city ranges.
+fn build_overlapping_map() -> IpAddrMap<Ipv4Addr, Country> {
+    let slot = u32::MAX / ENTRIES;
+    let countries: Vec<&Country> = all_countries().collect();
+
+    let rows = (0..ENTRIES)
+        .zip(countries.into_iter().cycle())
+        .map(|(index, country)| {
+            let start = index * slot;
+            let end = match index % 8 {
+                0 => start.saturating_add(slot * 8 - 1),
+                _ => start + slot / 4 * 3,
+            };
+
+            (
+                Ipv4Addr::from_bits(start),
+                Ipv4Addr::from_bits(end),
+                country.clone(),
+            )
+        });
+
+    IpAddrMap::from_rows(rows).expect("non-empty ranges")
+}
+
+/// Generate `count` pseudorandom IPv4 addresses with a fixed seed, so that runs are comparable.
+fn random_addresses(count: usize) -> Vec<Ipv4Addr> {
+    // xorshift32
+    let mut state: u32 = 0x9E37_79B9;
+
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            Ipv4Addr::from_bits(state)
+        })
+        .collect()
+}
+
+fn try_search(c: &mut Criterion) {
+    let map = build_map();
+    let addresses = random_addresses(QUERIES);
+
+    let mut group = c.benchmark_group("try_search");
+    group.throughput(Throughput::Elements(QUERIES as u64));
+    group.bench_function("ipv4_200k_random", |b| {
+        b.iter(|| {
+            for address in &addresses {
+                let _ = black_box(map.try_search(black_box(*address)));
+            }
+        })
+    });
+    group.finish();
+}
+
+/// Compare `search_sorted_batch` against searching each address of a sorted batch independently.
+fn search_sorted_batch(c: &mut Criterion) {
+    let map = build_map();
+    let mut addresses = random_addresses(BATCH);
+    addresses.sort_unstable();
+
+    let mut group = c.benchmark_group("sorted_batch");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    group.bench_function("try_search_each", |b| {
+        b.iter(|| {
+            black_box(&addresses)
+                .iter()
+                .map(|address| map.try_search(*address).ok())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("search_sorted_batch", |b| {
+        b.iter(|| map.search_sorted_batch(black_box(&addresses)))
+    });
+    group.finish();
+}
+
+/// Compare `IpAddrMap` against `IntervalMap`, with and without overlapping ranges, to choose
+/// between them.
+fn interval_map(c: &mut Criterion) {
+    let addresses = random_addresses(QUERIES);
+
+    let mut group = c.benchmark_group("interval_map");
+    group.throughput(Throughput::Elements(QUERIES as u64));
+
+    for (name, map) in [
+        ("disjoint", build_map()),
+        ("overlapping", build_overlapping_map()),
+    ] {
+        let interval_map = map.clone().into_interval_map();
+
+        group.bench_function(format!("{name}/ip_addr_map_try_search"), |b| {
+            b.iter(|| {
+                for address in &addresses {
+                    let _ = black_box(map.try_search(black_box(*address)));
+                }
+            })
+        });
+        group.bench_function(format!("{name}/interval_map_search"), |b| {
+            b.iter(|| {
+                for address in &addresses {
+                    let _ = black_box(interval_map.search(black_box(*address)));
+                }
+            })
+        });
+        group.bench_function(format!("{name}/ip_addr_map_search_all"), |b| {
+            b.iter(|| {
+                for address in &addresses {
+                    black_box(map.search_all(black_box(*address)));
+                }
+            })
+        });
+        group.bench_function(format!("{name}/interval_map_search_all"), |b| {
+            b.iter(|| {
+                for address in &addresses {
+                    black_box(interval_map.search_all(black_box(*address)));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, try_search, search_sorted_batch, interval_map);
+criterion_main!(benches);
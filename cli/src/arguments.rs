@@ -16,7 +16,8 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use ip_geo::parse::discover_database_paths;
 use serde::Deserialize;
 use std::{
     fmt::Display,
@@ -31,12 +32,36 @@ pub enum RunType {
     Ipv4,
     /// Resolve a given IPv6 address to a country.
     Ipv6,
+    /// Export the IPv4 and IPv6 databases in a given format.
+    Export(ExportFormat),
+    /// Check the IPv4 and IPv6 databases for problems, instead of resolving an address.
+    Validate,
+    /// Print the number of ranges and addresses of each country in the IPv4 and IPv6 databases.
+    Stats,
+    /// Print when the embedded country list was generated and how many countries it holds.
+    VersionDb,
     /// User did not select a path.
     None,
 }
 
 /// Inspect `arguments` to identify what `RunType` the user wants.
 pub fn get_run_type(arguments: &Arguments) -> RunType {
+    if arguments.version_db {
+        return RunType::VersionDb;
+    }
+
+    if arguments.validate {
+        return RunType::Validate;
+    }
+
+    if arguments.stats {
+        return RunType::Stats;
+    }
+
+    if let Some(format) = arguments.export {
+        return RunType::Export(format);
+    }
+
     if arguments.ipv4_addr.is_some() {
         return RunType::Ipv4;
     }
@@ -48,6 +73,15 @@ pub fn get_run_type(arguments: &Arguments) -> RunType {
     RunType::None
 }
 
+/// Represents the formats that the databases can be exported in.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// One JSON object per line, holding an IP address range and its country.
+    Jsonl,
+    /// The same as `Jsonl`, but with IP addresses as integers (ex. `16777216` for `1.0.0.0`).
+    JsonlBits,
+}
+
 /// Represents the command-line arguments of the program.
 #[derive(Parser, Deserialize, Debug, Clone)]
 #[command(about, version, long_about = None)]
@@ -87,6 +121,42 @@ pub struct Arguments {
     #[arg(long = "IPv6-comment")]
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv6_comment: Option<char>,
+
+    /// Log a single count of the database rows with unrecognized country codes, instead of a
+    /// warning for each row.
+    #[arg(short = 'q', long = "quiet", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quiet: Option<bool>,
+
+    /// Also print the matched IP address range and the database it came from.
+    #[arg(long = "explain")]
+    #[serde(skip, default)]
+    pub explain: bool,
+    /// Always parse the database, instead of loading it from or saving it to the cache.
+    #[arg(long = "no-cache")]
+    #[serde(skip, default)]
+    pub no_cache: bool,
+    /// Export the parsed IPv4 and IPv6 databases to stdout, instead of resolving an address.
+    #[arg(long = "export", value_enum)]
+    #[serde(skip, default)]
+    pub export: Option<ExportFormat>,
+    /// Check the databases for problems and report them, exiting non-zero if there are any errors.
+    ///
+    /// Checks only the databases given with `--IPv4-path` or `--IPv6-path`, or both if neither is.
+    #[arg(long = "validate")]
+    #[serde(skip, default)]
+    pub validate: bool,
+    /// Print the number of ranges and addresses of each country in the databases, sorted by the
+    /// number of addresses.
+    ///
+    /// Counts only the databases given with `--IPv4-path` or `--IPv6-path`, or both if neither is.
+    #[arg(long = "stats")]
+    #[serde(skip, default)]
+    pub stats: bool,
+    /// Print when the embedded country list was generated and how many countries it holds.
+    #[arg(long = "version-db")]
+    #[serde(skip, default)]
+    pub version_db: bool,
 }
 
 impl Display for Arguments {
@@ -110,7 +180,7 @@ pub fn get_config(arguments: Arguments) -> Arguments {
 
     let ipv4_path = arguments
         .ipv4_path
-        .unwrap_or_else(|| Path::new("/usr/share/tor/geoip").into());
+        .unwrap_or_else(|| discover_database_paths().0);
 
     let ipv4_len = arguments
         .ipv4_len
@@ -122,10 +192,15 @@ pub fn get_config(arguments: Arguments) -> Arguments {
         .or_else(|| from_config.as_ref().and_then(|v| v.ipv4_comment))
         .unwrap_or('#');
 
+    let quiet = arguments
+        .quiet
+        .or_else(|| from_config.as_ref().and_then(|v| v.quiet))
+        .unwrap_or(false);
+
     let ipv6_path = arguments
         .ipv6_path
         .or_else(|| from_config.as_ref().and_then(|v| v.ipv6_path.clone()))
-        .unwrap_or_else(|| Path::new("/usr/share/tor/geoip6").into());
+        .unwrap_or_else(|| discover_database_paths().1);
 
     let ipv6_len = arguments
         .ipv6_len
@@ -147,6 +222,13 @@ pub fn get_config(arguments: Arguments) -> Arguments {
         ipv6_path: Some(ipv6_path),
         ipv6_len: Some(ipv6_len),
         ipv6_comment: Some(ipv6_comment),
+        quiet: Some(quiet),
+        explain: arguments.explain,
+        no_cache: arguments.no_cache,
+        export: arguments.export,
+        validate: arguments.validate,
+        stats: arguments.stats,
+        version_db: arguments.version_db,
     }
 }
 
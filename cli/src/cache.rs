@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! An on-disk cache of parsed databases, so that repeated lookups can skip parsing.
+//!
+//! Each cache file holds the entries of one `IpAddrMap`, alongside a key identifying the database
+//! file it was parsed from. If the database changes, the key no longer matches, and the cache is
+//! rebuilt.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use ip_geo::{
+    country_list::{get_countries, Country},
+    IpAddrBits, IpAddrMap,
+};
+
+use crate::error::Error;
+
+/// The first bytes of every cache file, ending in the version of the format.
+const MAGIC: &[u8; 8] = b"IPGEO\0\0\x01";
+
+/// Return the path of the cache file with a given name, ex. `~/.cache/ip_geo/v4.bin`.
+///
+/// Returns `None` on platforms without a standard cache directory.
+pub fn cache_path(name: &str) -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("ip_geo").join(name))
+}
+
+/// Return the map cached at `cache` if it was parsed from the current version of `database` with
+/// the same `comment` character. Otherwise, parse it with `parse` and cache the result.
+///
+/// Failing to read or write the cache is not an error, only logged.
+pub fn load_or_parse<A: IpAddrBits>(
+    cache: &Path,
+    database: &Path,
+    comment: Option<char>,
+    parse: impl FnOnce() -> Result<IpAddrMap<A, Country>, Error>,
+) -> Result<IpAddrMap<A, Country>, Error> {
+    let Some(key) = cache_key(database, comment) else {
+        return parse();
+    };
+
+    if let Some(map) = fs::read(cache).ok().and_then(|bytes| decode(&bytes, &key)) {
+        log::info!("Loaded cached database from {}", cache.display());
+        return Ok(map);
+    }
+
+    let map = parse()?;
+
+    match write_cache(cache, &encode(&map, &key)) {
+        Ok(()) => log::info!("Cached database to {}", cache.display()),
+        Err(error) => log::warn!("Failed to cache database to {}: {error}", cache.display()),
+    }
+
+    Ok(map)
+}
+
+/// Identify the current version of `database`, by its path, size, and modification time, and the
+/// `comment` character it is parsed with.
+///
+/// Returns `None` if the file's metadata can't be read.
+fn cache_key(database: &Path, comment: Option<char>) -> Option<String> {
+    let path = fs::canonicalize(database).ok()?;
+    let metadata = fs::metadata(&path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    Some(format!(
+        "{}\n{}\n{}.{:09}\n{comment:?}",
+        path.display(),
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos(),
+    ))
+}
+
+/// Write `bytes` to `cache`, replacing any existing file only once it is completely written.
+fn write_cache(cache: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = cache.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let partial = cache.with_extension("partial");
+    fs::File::create(&partial)?.write_all(bytes)?;
+
+    fs::rename(partial, cache)
+}
+
+/// Serialize the entries of `map` alongside `key`, in the format:
+///
+/// ```text
+/// MAGIC, key length (u32), key, entry count (u64), entries
+/// ```
+///
+/// Where each entry is its start and end (each `A::BITS / 8` bytes), then its country code length
+/// (u8) and country code. All integers are little endian.
+fn encode<A: IpAddrBits>(map: &IpAddrMap<A, Country>, key: &str) -> Vec<u8> {
+    let address_len = A::BITS as usize / 8;
+    let mut bytes = Vec::with_capacity(MAGIC.len() + key.len() + map.len() * (address_len * 2 + 3));
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(key.as_bytes());
+    bytes.extend_from_slice(&(map.len() as u64).to_le_bytes());
+
+    for entry in map {
+        bytes.extend_from_slice(&entry.start().to_u128().to_le_bytes()[..address_len]);
+        bytes.extend_from_slice(&entry.end().to_u128().to_le_bytes()[..address_len]);
+
+        let code = entry.value().code.as_bytes();
+        bytes.push(code.len() as u8);
+        bytes.extend_from_slice(code);
+    }
+
+    bytes
+}
+
+/// Deserialize a map serialized by `encode`.
+///
+/// Returns `None` if `bytes` is not a valid cache file, or was not made with `key`.
+fn decode<A: IpAddrBits>(bytes: &[u8], key: &str) -> Option<IpAddrMap<A, Country>> {
+    /// Split `len` bytes off of the front of `bytes`.
+    fn take<'b>(bytes: &mut &'b [u8], len: usize) -> Option<&'b [u8]> {
+        let (taken, rest) = bytes.split_at_checked(len)?;
+        *bytes = rest;
+
+        Some(taken)
+    }
+
+    /// Split an address of `len` bytes off of the front of `bytes`.
+    fn take_address<A: IpAddrBits>(bytes: &mut &[u8], len: usize) -> Option<A> {
+        let mut address = [0; 16];
+        address[..len].copy_from_slice(take(bytes, len)?);
+
+        Some(A::from_u128(u128::from_le_bytes(address)))
+    }
+
+    let mut bytes = bytes;
+    let address_len = A::BITS as usize / 8;
+
+    if take(&mut bytes, MAGIC.len())? != MAGIC {
+        return None;
+    }
+
+    let key_len = u32::from_le_bytes(take(&mut bytes, 4)?.try_into().ok()?);
+    if take(&mut bytes, key_len as usize)? != key.as_bytes() {
+        return None;
+    }
+
+    let len = u64::from_le_bytes(take(&mut bytes, 8)?.try_into().ok()?);
+    let countries = get_countries();
+
+    // Don't trust `len` further than the remaining bytes could hold, in case the file is corrupt
+    let max_len = bytes.len() / (2 * address_len + 1);
+    let mut map = IpAddrMap::new_with_capacity(usize::try_from(len).ok()?.min(max_len));
+
+    for _ in 0..len {
+        let start = take_address(&mut bytes, address_len)?;
+        let end = take_address(&mut bytes, address_len)?;
+
+        let code_len = take(&mut bytes, 1)?[0];
+        let code = std::str::from_utf8(take(&mut bytes, code_len as usize)?).ok()?;
+        let country = countries.get(code)?.clone();
+
+        map.insert_range(start, end, country).ok()?;
+    }
+
+    if !bytes.is_empty() {
+        return None;
+    }
+
+    map.cleanup();
+    Some(map)
+}
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+/// Represents all possible error states of this crate.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("comment character '{0}' is not ASCII")]
+    NonAsciiComment(char),
+
+    #[error(transparent)]
+    IpGeo(#[from] ip_geo::Error),
+    #[error("failed to perform I/O action")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to serialize JSON data")]
+    JsonSerialize(#[from] serde_json::Error),
+}
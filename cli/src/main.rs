@@ -18,79 +18,548 @@
 
 #![allow(dead_code)]
 
-use clap::Parser;
-use ip_geo::{country_list::Country, Error};
+use std::{
+    fmt::Display,
+    io::{self, BufWriter, Write},
+    net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use clap::{CommandFactory, Parser};
+use ip_geo::{
+    country_list::Country,
+    parse::{ParseOptions, ParseReport},
+    IpAddrBits, IpAddrEntry, IpAddrMap,
+};
+use serde::Serialize;
 
 mod arguments;
-use arguments::{Arguments, RunType};
+
+mod cache;
+
+use arguments::{Arguments, ExportFormat, RunType};
+
+mod error;
+use error::Error;
 
 fn main() {
-    let arguments = arguments::get_config(Arguments::parse());
+    // Log `warn` and above, unless overridden by `RUST_LOG`
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let arguments = Arguments::parse();
+    // Before defaults are inserted, so `--validate` and `--stats` read only the databases the user
+    // named
+    let select_both = arguments.ipv4_path.is_none() && arguments.ipv6_path.is_none();
+    let select_ipv4 = select_both || arguments.ipv4_path.is_some();
+    let select_ipv6 = select_both || arguments.ipv6_path.is_some();
+
+    let arguments = arguments::get_config(arguments);
+    let explain = arguments.explain;
 
     match arguments::get_run_type(&arguments) {
-        RunType::Ipv4 => print_country(find_ipv4(arguments)),
-        RunType::Ipv6 => print_country(find_ipv6(arguments)),
-        RunType::None => todo!("Trigger help message"),
+        RunType::Ipv4 => {
+            let database = arguments.ipv4_path.clone().filter(|_| explain);
+            print_entry(find_ipv4(arguments), database.as_deref());
+        }
+        RunType::Ipv6 => {
+            let database = arguments.ipv6_path.clone().filter(|_| explain);
+            print_entry(find_ipv6(arguments), database.as_deref());
+        }
+        RunType::Export(format) => {
+            let as_bits = matches!(format, ExportFormat::JsonlBits);
+            let output = BufWriter::new(io::stdout().lock());
+
+            if let Err(error) = export_jsonl(&arguments, as_bits, output) {
+                eprintln!("{error}");
+            }
+        }
+        RunType::Validate => {
+            let mut output = io::stdout().lock();
+            let mut valid = true;
+
+            if select_ipv4 {
+                let database = arguments.ipv4_path.as_deref().expect("A valid IPv4 path");
+                let parse = || parse_ipv4_with_report(&arguments);
+
+                match write_validation("IPv4", database, parse, &mut output) {
+                    Ok(ipv4_valid) => valid &= ipv4_valid,
+                    Err(error) => eprintln!("{error}"),
+                }
+            }
+
+            if select_ipv6 {
+                let database = arguments.ipv6_path.as_deref().expect("A valid IPv6 path");
+                let parse = || parse_ipv6_with_report(&arguments);
+
+                match write_validation("IPv6", database, parse, &mut output) {
+                    Ok(ipv6_valid) => valid &= ipv6_valid,
+                    Err(error) => eprintln!("{error}"),
+                }
+            }
+
+            if !valid {
+                std::process::exit(1);
+            }
+        }
+        RunType::Stats => {
+            let mut output = io::stdout().lock();
+
+            if select_ipv4 {
+                let database = arguments.ipv4_path.as_deref().expect("A valid IPv4 path");
+                // Parsing panics if the database does not exist
+                let result = std::fs::metadata(database)
+                    .map_err(Error::from)
+                    .and_then(|_| parse_ipv4_with_report(&arguments))
+                    .and_then(|(map, _)| write_stats("IPv4", database, &map, &mut output));
+
+                if let Err(error) = result {
+                    eprintln!("{error}");
+                }
+            }
+
+            if select_ipv6 {
+                let database = arguments.ipv6_path.as_deref().expect("A valid IPv6 path");
+                // Parsing panics if the database does not exist
+                let result = std::fs::metadata(database)
+                    .map_err(Error::from)
+                    .and_then(|_| parse_ipv6_with_report(&arguments))
+                    .and_then(|(map, _)| write_stats("IPv6", database, &map, &mut output));
+
+                if let Err(error) = result {
+                    eprintln!("{error}");
+                }
+            }
+        }
+        RunType::VersionDb => {
+            let (generated_at, count) = ip_geo::country_list::provenance();
+            println!("country list: {count} entries, generated {generated_at}");
+        }
+        RunType::None => Arguments::command()
+            .print_help()
+            .expect("help text printed to stdout"),
+    }
+}
+
+/// For the result of a lookup, print the output of `format_country` to stdout, or any error to
+/// stderr.
+///
+/// If `database` is provided, also print the output of `explain_entry`.
+fn print_entry<A: IpAddrBits + Display>(
+    entry: Result<IpAddrEntry<A, Country>, Error>,
+    database: Option<&Path>,
+) {
+    let explanation = match (&entry, database) {
+        (Ok(entry), Some(database)) => Some(explain_entry(entry, database)),
+        _ => None,
+    };
+
+    match format_country(entry.map(|entry| entry.unwrap().2)) {
+        Ok(output) => println!("{output}"),
+        Err(error) => eprintln!("{error}"),
+    }
+
+    if let Some(explanation) = explanation {
+        println!("{explanation}");
     }
 }
 
-/// For a given `Country`, print ISO 3166-1 alpha-2 code and a country name (ex. `BE Belgium`).
-fn print_country(country: Result<Country, Error>) {
+/// For the result of a lookup, return the text to display to the user.
+///
+/// For a `Country`, returns its ISO 3166-1 alpha-2 code and its name (ex. `BE Belgium`). If no
+/// country was found, returns a message saying so. All other errors are passed through.
+fn format_country(country: Result<Country, Error>) -> Result<String, Error> {
     match country {
-        Ok(country) => println!("{} {}", country.code, country.name),
-        Err(error) => match error {
-            Error::NoValueFound => println!("No country found!"),
-            _ => eprintln!("{error}"),
-        },
+        Ok(country) => Ok(country.to_string()),
+        Err(Error::IpGeo(ip_geo::Error::NoValueFound)) => Ok("No country found!".to_string()),
+        Err(error) => Err(error),
     }
 }
 
-/// For a given IPv4 address (contained in `arguments`), find the country it is associated with.
-fn find_ipv4(arguments: Arguments) -> Result<Country, Error> {
-    let mut ipv4_map = ip_geo::ipv4::parse_ipv4_file(
+/// For a matched entry from a given database, describe the IP address range, its CIDR
+/// decomposition, and the database.
+///
+/// For example:
+///
+/// ```text
+/// Range: 1.0.0.0–1.255.255.255
+/// CIDR: 1.0.0.0/8
+/// Database: /usr/share/tor/geoip
+/// ```
+fn explain_entry<A: IpAddrBits + Display>(
+    entry: &IpAddrEntry<A, Country>,
+    database: &Path,
+) -> String {
+    let cidrs = entry
+        .to_cidrs()
+        .iter()
+        .map(|(network, prefix)| format!("{network}/{prefix}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "Range: {}–{}\nCIDR: {cidrs}\nDatabase: {}",
+        entry.start(),
+        entry.end(),
+        database.display()
+    )
+}
+
+/// For a given IPv4 address (contained in `arguments`), find the entry it is associated with.
+fn find_ipv4(arguments: Arguments) -> Result<IpAddrEntry<Ipv4Addr, Country>, Error> {
+    let mut ipv4_map = load_ipv4(&arguments)?;
+
+    let input_addr = arguments.ipv4_addr.expect("A valid IPv4 Address");
+
+    Ok(ipv4_map.search_entry(input_addr).cloned()?)
+}
+
+/// Load the IPv4 database (specified in `arguments`) from the cache, parsing it only if the cache
+/// is missing or out of date. If `--no-cache` is given, always parses it.
+fn load_ipv4(arguments: &Arguments) -> Result<IpAddrMap<Ipv4Addr, Country>, Error> {
+    match cache::cache_path("v4.bin").filter(|_| !arguments.no_cache) {
+        Some(cache) => {
+            let database = arguments
+                .ipv4_path
+                .as_deref()
+                .expect("A valid path to an IPv4 GeoIP database");
+
+            cache::load_or_parse(&cache, database, arguments.ipv4_comment, || {
+                parse_ipv4(arguments)
+            })
+        }
+        None => parse_ipv4(arguments),
+    }
+}
+
+/// Parse the IPv4 database (specified in `arguments`).
+fn parse_ipv4(arguments: &Arguments) -> Result<IpAddrMap<Ipv4Addr, Country>, Error> {
+    let (map, report) = parse_ipv4_with_report(arguments)?;
+    log::info!("IPv4 database: {report}");
+
+    Ok(map)
+}
+
+/// Parse the IPv4 database (specified in `arguments`), also returning the report of skipped rows.
+fn parse_ipv4_with_report(
+    arguments: &Arguments,
+) -> Result<(IpAddrMap<Ipv4Addr, Country>, ParseReport), Error> {
+    Ok(ip_geo::ipv4::parse_ipv4_file(
         arguments
             .ipv4_path
+            .clone()
             .expect("A valid path to an IPv4 GeoIP database"),
         arguments
             .ipv4_len
             .expect("The number of lines in the IPv4 GeoIP database"),
-        arguments.ipv4_comment,
-    );
+        ParseOptions {
+            comment: arguments.ipv4_comment.map(char_to_byte).transpose()?,
+            quiet: arguments.quiet.unwrap_or(false),
+            ..Default::default()
+        },
+    )?)
+}
 
-    let input_addr = arguments.ipv4_addr.expect("A valid IPv4 Address");
+/// For a given IPv6 address (contained in `arguments`), find the entry it is associated with.
+fn find_ipv6(arguments: Arguments) -> Result<IpAddrEntry<Ipv6Addr, Country>, Error> {
+    let mut ipv6_map = load_ipv6(&arguments)?;
+
+    let input_addr = arguments.ipv6_addr.expect("A valid IPv6 Address");
+
+    Ok(ipv6_map.search_entry(input_addr).cloned()?)
+}
+
+/// Load the IPv6 database (specified in `arguments`) from the cache, parsing it only if the cache
+/// is missing or out of date. If `--no-cache` is given, always parses it.
+fn load_ipv6(arguments: &Arguments) -> Result<IpAddrMap<Ipv6Addr, Country>, Error> {
+    match cache::cache_path("v6.bin").filter(|_| !arguments.no_cache) {
+        Some(cache) => {
+            let database = arguments
+                .ipv6_path
+                .as_deref()
+                .expect("A valid path to an IPv6 GeoIP database");
+
+            cache::load_or_parse(&cache, database, arguments.ipv6_comment, || {
+                parse_ipv6(arguments)
+            })
+        }
+        None => parse_ipv6(arguments),
+    }
+}
 
-    ipv4_map.search(input_addr).cloned()
+/// Parse the IPv6 database (specified in `arguments`).
+fn parse_ipv6(arguments: &Arguments) -> Result<IpAddrMap<Ipv6Addr, Country>, Error> {
+    let (map, report) = parse_ipv6_with_report(arguments)?;
+    log::info!("IPv6 database: {report}");
+
+    Ok(map)
 }
 
-/// For a given IPv6 address (contained in `arguments`), find the country it is associated with.
-fn find_ipv6(arguments: Arguments) -> Result<Country, Error> {
-    let mut ipv6_map = ip_geo::ipv6::parse_ipv6_file(
+/// Parse the IPv6 database (specified in `arguments`), also returning the report of skipped rows.
+fn parse_ipv6_with_report(
+    arguments: &Arguments,
+) -> Result<(IpAddrMap<Ipv6Addr, Country>, ParseReport), Error> {
+    Ok(ip_geo::ipv6::parse_ipv6_file(
         arguments
             .ipv6_path
+            .clone()
             .expect("A valid path to an IPv6 GeoIP database"),
         arguments
             .ipv6_len
             .expect("The number of lines in the IPv6 GeoIP database"),
-        arguments.ipv6_comment,
-    );
+        ParseOptions {
+            comment: arguments.ipv6_comment.map(char_to_byte).transpose()?,
+            quiet: arguments.quiet.unwrap_or(false),
+            ..Default::default()
+        },
+    )?)
+}
 
-    let input_addr = arguments.ipv6_addr.expect("A valid IPv6 Address");
+/// Write every entry of the IPv4 and IPv6 databases (specified in `arguments`) to `output` as JSON
+/// Lines, using `write_jsonl`.
+fn export_jsonl(arguments: &Arguments, as_bits: bool, mut output: impl Write) -> Result<(), Error> {
+    write_jsonl(&parse_ipv4(arguments)?, as_bits, &mut output)?;
+    write_jsonl(&parse_ipv6(arguments)?, as_bits, &mut output)?;
+
+    Ok(output.flush()?)
+}
+
+/// Write every entry of `map` to `output` as a line of JSON.
+///
+/// For example:
+///
+/// ```json
+/// {"start":"1.0.0.0","end":"1.0.0.255","country":{"code":"BE","name":"Belgium","flag":"🇧🇪","coordinates":[4.668055555,50.641111111],"timezones":[],"calling_code":null}}
+/// ```
+///
+/// If `as_bits` is true, IP addresses are written as integers instead, ex. `"start":16777216`.
+fn write_jsonl<A: IpAddrBits + Serialize>(
+    map: &IpAddrMap<A, Country>,
+    as_bits: bool,
+    mut output: impl Write,
+) -> Result<(), Error> {
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum SerializableAddress<'e, A> {
+        Address(&'e A),
+        Bits(u128),
+    }
+
+    #[derive(Serialize)]
+    struct SerializableEntry<'e, A> {
+        start: SerializableAddress<'e, A>,
+        end: SerializableAddress<'e, A>,
+        country: &'e Country,
+    }
+
+    impl<'e, A: IpAddrBits> SerializableAddress<'e, A> {
+        fn new(address: &'e A, as_bits: bool) -> Self {
+            if as_bits {
+                Self::Bits(address.to_u128())
+            } else {
+                Self::Address(address)
+            }
+        }
+    }
+
+    for entry in map {
+        let entry = SerializableEntry {
+            start: SerializableAddress::new(entry.start(), as_bits),
+            end: SerializableAddress::new(entry.end(), as_bits),
+            country: entry.value(),
+        };
+
+        serde_json::to_writer(&mut output, &entry)?;
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+/// Write the number of ranges and addresses of each country in a database to `output`, sorted by
+/// the number of addresses, then by country code.
+///
+/// For example:
+///
+/// ```text
+/// IPv4 database /usr/share/tor/geoip:
+///   code     ranges                                addresses  name
+///   US        51234                               1598125312  United States
+///   CN         8655                                343024128  China
+/// ```
+fn write_stats<A: IpAddrBits>(
+    name: &str,
+    database: &Path,
+    map: &IpAddrMap<A, Country>,
+    mut output: impl Write,
+) -> Result<(), Error> {
+    let mut counts: Vec<_> = map.count_by(Country::clone).into_iter().collect();
+    counts.sort_unstable_by(|(a_country, (_, a)), (b_country, (_, b))| {
+        b.cmp(a).then_with(|| a_country.code.cmp(&b_country.code))
+    });
+
+    writeln!(output, "{name} database {}:", database.display())?;
+    writeln!(output, "  code     ranges  {:>39}  name", "addresses")?;
+
+    for (country, (ranges, addresses)) in counts {
+        writeln!(
+            output,
+            "  {:<4} {ranges:>10}  {addresses:>39}  {}",
+            country.code, country.name
+        )?;
+    }
+
+    Ok(())
+}
 
-    ipv6_map.search(input_addr).cloned()
+/// Check a database for problems, writing a report to `output`.
+///
+/// `parse` is only called once the database is known to exist, as parsing panics otherwise.
+///
+/// For example:
+///
+/// ```text
+/// IPv4 database /usr/share/tor/geoip:
+///   loaded 198,231 ranges, skipped 1,769 (unknown: 1,700, placeholder: 69, malformed: 0)
+///   warning: 1700 rows with unknown country codes
+///   warning: 3 duplicate ranges
+///   error: 2 overlapping ranges, first at 1.0.0.0–1.0.0.255 and 1.0.0.128–1.0.1.255
+///   coverage: 87.42% of the address space
+/// ```
+///
+/// A database that fails to parse (ex. because a range is inverted, with `start > end`), malformed
+/// rows, and overlapping ranges are errors. Unknown country codes and duplicate ranges are
+/// warnings. Because the map is sorted while parsing, out of order rows are not reported.
+///
+/// Returns true if there were no errors.
+fn write_validation<A: IpAddrBits + Display>(
+    name: &str,
+    database: &Path,
+    parse: impl FnOnce() -> Result<(IpAddrMap<A, Country>, ParseReport), Error>,
+    mut output: impl Write,
+) -> Result<bool, Error> {
+    writeln!(output, "{name} database {}:", database.display())?;
+
+    if let Err(error) = std::fs::metadata(database) {
+        writeln!(output, "  error: failed to open database: {error}")?;
+        return Ok(false);
+    }
+
+    let (map, report) = match parse() {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            writeln!(output, "  error: failed to parse database: {error}")?;
+            return Ok(false);
+        }
+    };
+
+    let mut valid = true;
+    writeln!(output, "  {report}")?;
+
+    if report.skipped_malformed != 0 {
+        valid = false;
+        writeln!(
+            output,
+            "  error: {} malformed rows",
+            report.skipped_malformed
+        )?;
+    }
+
+    if report.skipped_unknown_code != 0 {
+        let unknown = report.skipped_unknown_code;
+        writeln!(
+            output,
+            "  warning: {unknown} rows with unknown country codes"
+        )?;
+    }
+
+    let duplicates = report.inserted - map.len();
+    if duplicates != 0 {
+        writeln!(output, "  warning: {duplicates} duplicate ranges")?;
+    }
+
+    // Compare against the range reaching furthest so far, as a range can overlap more than its
+    // immediate neighbor
+    let mut furthest: Option<&IpAddrEntry<A, Country>> = None;
+    let mut overlaps = map.iter().filter_map(|entry| {
+        let overlap = furthest
+            .filter(|a| a.end() >= entry.start())
+            .map(|a| (a, entry));
+
+        if furthest.is_none_or(|a| entry.end() > a.end()) {
+            furthest = Some(entry);
+        }
+
+        overlap
+    });
+
+    if let Some((a, b)) = overlaps.next() {
+        valid = false;
+        writeln!(
+            output,
+            "  error: {} overlapping ranges, first at {}–{} and {}–{}",
+            overlaps.count() + 1,
+            a.start(),
+            a.end(),
+            b.start(),
+            b.end(),
+        )?;
+    }
+
+    // Count each address once, even if it is in overlapping ranges
+    let mut covered = 0.0;
+    let mut next_uncovered = Some(0_u128);
+    for entry in &map {
+        let (start, end) = (entry.start().to_u128(), entry.end().to_u128());
+
+        if let Some(next) = next_uncovered.filter(|&next| next <= end) {
+            covered += (end - start.max(next)) as f64 + 1.0;
+            next_uncovered = end.checked_add(1);
+        }
+    }
+
+    let coverage = covered / 2_f64.powi(A::BITS as i32) * 100.0;
+    writeln!(output, "  coverage: {coverage:.2}% of the address space")?;
+
+    Ok(valid)
 }
 
-/// Lossily converts a char to a byte.
+/// Converts an ASCII char to a byte.
 ///
-/// Where a char is multiple bytes, it returns only the first byte.
-fn char_to_byte(char: char) -> u8 {
-    // How could this be improved?
-    char.to_string().as_bytes().first().unwrap().to_owned()
+/// Returns `Error::NonAsciiComment` if `char` is not ASCII, as it could never match the first
+/// byte of a line.
+fn char_to_byte(char: char) -> Result<u8, Error> {
+    match char.is_ascii() {
+        true => Ok(char as u8),
+        false => Err(Error::NonAsciiComment(char)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Returns `Arguments` with nothing set and the cache disabled, for tests to override.
+    fn test_arguments() -> Arguments {
+        Arguments {
+            config_path: None,
+            ipv4_addr: None,
+            ipv4_path: None,
+            ipv4_len: None,
+            ipv4_comment: None,
+            ipv6_addr: None,
+            ipv6_path: None,
+            ipv6_len: None,
+            ipv6_comment: None,
+            quiet: None,
+            explain: false,
+            no_cache: true,
+            export: None,
+            validate: false,
+            stats: false,
+            version_db: false,
+        }
+    }
+
     #[test]
     fn test_find_ipv4() {
         use std::{io::Write, net::Ipv4Addr, path::Path};
@@ -119,20 +588,19 @@ mod tests {
 
         fn gen_args(addr: Ipv4Addr, path: Box<Path>) -> arguments::Arguments {
             Arguments {
-                config_path: None,
                 ipv4_addr: Some(addr),
                 ipv4_path: Some(path),
                 ipv4_len: Some(2),
-                ipv4_comment: None,
-                ipv6_addr: None,
-                ipv6_path: None,
-                ipv6_len: None,
-                ipv6_comment: None,
+                ..test_arguments()
             }
         }
 
         fn get_code(addr: Ipv4Addr, path: Box<Path>) -> std::sync::Arc<str> {
-            find_ipv4(gen_args(addr, path)).unwrap().code
+            find_ipv4(gen_args(addr, path))
+                .unwrap()
+                .value()
+                .code
+                .clone()
         }
 
         assert_eq!(get_code(middle_a, path.clone()), value_a);
@@ -164,23 +632,288 @@ mod tests {
 
         fn gen_args(addr: Ipv6Addr, path: Box<Path>) -> arguments::Arguments {
             Arguments {
-                config_path: None,
-                ipv4_addr: None,
-                ipv4_path: None,
-                ipv4_len: None,
-                ipv4_comment: None,
                 ipv6_addr: Some(addr),
                 ipv6_path: Some(path),
                 ipv6_len: Some(2),
-                ipv6_comment: None,
+                ..test_arguments()
             }
         }
 
         fn get_code(addr: Ipv6Addr, path: Box<Path>) -> std::sync::Arc<str> {
-            find_ipv6(gen_args(addr, path)).unwrap().code
+            find_ipv6(gen_args(addr, path))
+                .unwrap()
+                .value()
+                .code
+                .clone()
         }
 
         assert_eq!(get_code(middle_a, path.clone()), value_a);
         assert_eq!(get_code(middle_b, path.clone()), value_b);
     }
+
+    #[test]
+    fn test_find_ipv4_with_ipv6_database() {
+        use std::{io::Write, path::Path};
+
+        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+        write!(temp_file, "1::,3::,BE\n4::,6::,CA\n").unwrap();
+        let path: Box<Path> = temp_file.path().into();
+
+        let arguments = Arguments {
+            ipv4_addr: Some(Ipv4Addr::new(2, 2, 2, 2)),
+            ipv4_path: Some(path),
+            ipv4_len: Some(2),
+            ..test_arguments()
+        };
+
+        assert!(matches!(
+            find_ipv4(arguments),
+            Err(Error::IpGeo(ip_geo::Error::WrongDatabaseFormat {
+                failed: 2,
+                total: 2
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_find_ipv6_uses_ipv6_comment() {
+        use std::{io::Write, net::Ipv6Addr, path::Path, str::FromStr};
+
+        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+        write!(temp_file, "; not a valid line\n1::,3::,BE\n").unwrap();
+        let path: Box<Path> = temp_file.path().into();
+
+        let arguments = Arguments {
+            ipv4_comment: Some('#'),
+            ipv6_addr: Some(Ipv6Addr::from_str("2::").unwrap()),
+            ipv6_path: Some(path),
+            ipv6_len: Some(1),
+            ipv6_comment: Some(';'),
+            ..test_arguments()
+        };
+
+        assert_eq!(find_ipv6(arguments).unwrap().value().code, "BE".into());
+    }
+
+    #[test]
+    fn test_write_validation() {
+        let temp_file = tempfile::NamedTempFile::new().unwrap();
+        let database = temp_file.path();
+
+        let entry = |start: [u8; 4], end: [u8; 4], code: &str| {
+            IpAddrEntry::new(
+                Ipv4Addr::from(start),
+                Ipv4Addr::from(end),
+                code.parse::<Country>().unwrap(),
+            )
+            .unwrap()
+        };
+
+        let mut map: IpAddrMap<Ipv4Addr, Country> = IpAddrMap::new();
+        map.insert(entry([0, 0, 0, 0], [127, 255, 255, 255], "BE"));
+        map.insert(entry([0, 0, 0, 0], [127, 255, 255, 255], "BE"));
+        let report = ParseReport {
+            total_rows: 3,
+            inserted: 2,
+            skipped_unknown_code: 1,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        assert!(
+            write_validation("IPv4", database, || Ok((map.clone(), report)), &mut output).unwrap()
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("warning: 1 rows with unknown country codes"));
+        assert!(output.contains("warning: 1 duplicate ranges"));
+        assert!(output.contains("coverage: 50.00% of the address space"));
+
+        map.insert(entry([127, 0, 0, 0], [255, 255, 255, 255], "CA"));
+        let report = ParseReport {
+            total_rows: 3,
+            inserted: 3,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        assert!(!write_validation("IPv4", database, || Ok((map, report)), &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "error: 1 overlapping ranges, first at 0.0.0.0–127.255.255.255 and 127.0.0.0–255.255.255.255"
+        ));
+        assert!(output.contains("coverage: 100.00% of the address space"));
+
+        // Ranges overlapping a wide range but not each other are still counted
+        let mut map: IpAddrMap<Ipv4Addr, Country> = IpAddrMap::new();
+        map.insert(entry([0, 0, 0, 0], [255, 255, 255, 255], "BE"));
+        map.insert(entry([1, 0, 0, 0], [1, 0, 0, 255], "CA"));
+        map.insert(entry([2, 0, 0, 0], [2, 0, 0, 255], "DE"));
+        let report = ParseReport {
+            total_rows: 3,
+            inserted: 3,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        assert!(!write_validation("IPv4", database, || Ok((map, report)), &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "error: 2 overlapping ranges, first at 0.0.0.0–255.255.255.255 and 1.0.0.0–1.0.0.255"
+        ));
+
+        let inverted = Err(Error::IpGeo(ip_geo::Error::EmptyRangeError));
+        let mut output = Vec::new();
+        assert!(!write_validation::<Ipv4Addr>("IPv4", database, || inverted, &mut output).unwrap());
+    }
+
+    #[test]
+    fn test_write_stats() {
+        let entry = |start: [u8; 4], end: [u8; 4], code: &str| {
+            IpAddrEntry::new(
+                Ipv4Addr::from(start),
+                Ipv4Addr::from(end),
+                code.parse::<Country>().unwrap(),
+            )
+            .unwrap()
+        };
+
+        let mut map: IpAddrMap<Ipv4Addr, Country> = IpAddrMap::new();
+        map.insert(entry([1, 0, 0, 0], [1, 0, 0, 255], "BE"));
+        map.insert(entry([2, 0, 0, 0], [2, 0, 255, 255], "CA"));
+        map.insert(entry([3, 0, 0, 0], [3, 0, 0, 15], "BE"));
+
+        let mut output = Vec::new();
+        write_stats("IPv4", Path::new("geoip"), &map, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+
+        assert_eq!(lines[0], "IPv4 database geoip:");
+        assert!(lines[2].starts_with("  CA            1") && lines[2].ends_with(" 65536  Canada"));
+        assert!(lines[3].starts_with("  BE            2") && lines[3].ends_with(" 272  Belgium"));
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_cache_load_or_parse() {
+        use std::{cell::Cell, io::Write};
+
+        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(temp_file, "1::,3::,BE").unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = cache_dir.path().join("v6.bin");
+
+        let arguments = Arguments {
+            ipv6_path: Some(temp_file.path().into()),
+            ipv6_len: Some(1),
+            ipv6_comment: Some('#'),
+            no_cache: false,
+            ..test_arguments()
+        };
+
+        let parses = Cell::new(0);
+        let load = |comment| {
+            cache::load_or_parse(&cache, temp_file.path(), comment, || {
+                parses.set(parses.get() + 1);
+                parse_ipv6(&arguments)
+            })
+            .unwrap()
+        };
+
+        let middle = "2::".parse::<Ipv6Addr>().unwrap();
+        assert_eq!(
+            load(Some('#')).try_search(middle).unwrap().code,
+            "BE".into()
+        );
+        assert_eq!(
+            load(Some('#')).try_search(middle).unwrap().code,
+            "BE".into()
+        );
+        assert_eq!(parses.get(), 1);
+
+        // A corrupt entry count invalidates the cache, rather than allocating for it
+        let mut bytes = std::fs::read(&cache).unwrap();
+        let count = bytes.len() - (2 * 16 + 1 + "BE".len()) - 8;
+        bytes[count..count + 8].fill(0xff);
+        std::fs::write(&cache, bytes).unwrap();
+        load(Some('#'));
+        assert_eq!(parses.get(), 2);
+
+        // Parsing with different options invalidates the cache
+        load(None);
+        assert_eq!(parses.get(), 3);
+    }
+
+    #[test]
+    fn test_format_country() {
+        let belgium = "BE".parse::<Country>().unwrap();
+        assert_eq!(format_country(Ok(belgium)).unwrap(), "BE Belgium");
+
+        let not_found = Err(Error::IpGeo(ip_geo::Error::NoValueFound));
+        assert_eq!(format_country(not_found).unwrap(), "No country found!");
+
+        let non_ascii = Err(Error::NonAsciiComment('’'));
+        assert!(format_country(non_ascii).is_err());
+    }
+
+    #[test]
+    fn test_explain_entry() {
+        let entry = IpAddrEntry::new(
+            Ipv4Addr::new(1, 0, 0, 0),
+            Ipv4Addr::new(1, 0, 1, 255),
+            "BE".parse::<Country>().unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            explain_entry(&entry, Path::new("/usr/share/tor/geoip")),
+            "Range: 1.0.0.0–1.0.1.255\nCIDR: 1.0.0.0/23\nDatabase: /usr/share/tor/geoip"
+        );
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let mut map = IpAddrMap::new();
+        map.insert(
+            IpAddrEntry::new(
+                Ipv4Addr::new(1, 0, 0, 0),
+                Ipv4Addr::new(1, 0, 0, 255),
+                "BE".parse::<Country>().unwrap(),
+            )
+            .unwrap(),
+        );
+        map.cleanup();
+
+        let mut output = vec![];
+        write_jsonl(&map, false, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"{"start":"1.0.0.0","end":"1.0.0.255","country":"#,
+                r#"{"code":"BE","name":"Belgium","flag":"🇧🇪","coordinates":[4.668055555,50.641111111],"timezones":[],"calling_code":null}}"#,
+                "\n"
+            )
+        );
+
+        let mut output = vec![];
+        write_jsonl(&map, true, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"{"start":16777216,"end":16777471,"country":"#,
+                r#"{"code":"BE","name":"Belgium","flag":"🇧🇪","coordinates":[4.668055555,50.641111111],"timezones":[],"calling_code":null}}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_char_to_byte() {
+        assert_eq!(char_to_byte('#').unwrap(), b'#');
+        assert!(matches!(
+            char_to_byte('’'),
+            Err(Error::NonAsciiComment('’'))
+        ));
+    }
 }
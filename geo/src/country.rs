@@ -16,53 +16,116 @@
 // not, see <https://www.gnu.org/licenses/>.
 
 use std::{
+    collections::HashMap,
     fmt::{Display, Write},
     str::FromStr,
 };
 
-use crate::{wikidata, Error};
+use crate::{wikidata, Arguments, Error};
 
 /// Represents a country and its ISO 3166-1 alpha-2 code, alongside a Wikidata ID (if available).
 #[derive(Debug, Clone)]
 pub struct Country {
-    pub name: Box<str>,          // Ex. Belgium
-    pub code: Box<str>,          // Ex. BE
-    pub coordinates: (f64, f64), // Ex. (4.668055555, 50.641111111)
+    pub name: Box<str>,                     // Ex. Belgium
+    pub code: Box<str>,                     // Ex. BE
+    pub coordinates: (f64, f64),            // Ex. (4.668055555, 50.641111111)
+    pub names: HashMap<Box<str>, Box<str>>, // Ex. {"fr": "Belgique"}
+    pub timezones: Box<[Box<str>]>,         // Ex. ["Europe/Brussels"]
+    pub calling_code: Option<Box<str>>,     // Ex. +32
 }
 
 impl Country {
     /// Create a new `Country`.
-    pub fn new(code: impl AsRef<str>, name: impl AsRef<str>, coordinates: (f64, f64)) -> Self {
+    pub fn new(
+        code: impl AsRef<str>,
+        name: impl AsRef<str>,
+        coordinates: (f64, f64),
+        names: HashMap<Box<str>, Box<str>>,
+        timezones: Box<[Box<str>]>,
+        calling_code: Option<Box<str>>,
+    ) -> Self {
         Self {
             name: name.as_ref().into(),
             code: code.as_ref().into(),
             coordinates,
+            names,
+            timezones,
+            calling_code,
         }
     }
 
-    /// Create a new `Country` from a `CountryPair` and a Wikidata query using `CountryPair.code`.
-    pub fn from_pair(pair: &CountryPair) -> Self {
+    /// Create a new `Country` from a `CountryPair` and Wikidata queries using `CountryPair.code`.
+    ///
+    /// Fetches the country's name in each of `arguments.languages`, its time zones if
+    /// `arguments.timezones` is set, and its calling code if `arguments.calling_codes` is set.
+    pub fn from_pair(pair: &CountryPair, arguments: &Arguments) -> Self {
         let name = pair.name.clone();
         let code = pair.code.clone();
         let coordinates = wikidata::query_for_coords_by_code(&code);
+        let names = wikidata::query_for_names_by_code(&code, &arguments.languages);
+        let timezones = match arguments.timezones {
+            true => wikidata::query_for_timezones_by_code(&code),
+            false => [].into(),
+        };
+        let calling_code = match arguments.calling_codes {
+            true => wikidata::query_for_calling_code_by_code(&code),
+            false => None,
+        };
 
         Self {
             name,
             code,
             coordinates,
+            names,
+            timezones,
+            calling_code,
         }
     }
 
-    /// Create a new `Country` from a `CountryPair` and a Wikidata query using `id`.
-    pub fn from_pair_and_id(pair: &CountryPair, id: impl AsRef<str>) -> Self {
+    /// Create a new `Country` from a `CountryPair` alone, without any network access.
+    ///
+    /// `coordinates` is set to `(0.0, 0.0)`, and the country has no localized names, time zones,
+    /// or calling code.
+    pub fn from_pair_offline(pair: &CountryPair) -> Self {
+        Self::new(
+            &pair.code,
+            &pair.name,
+            (0.0, 0.0),
+            HashMap::new(),
+            [].into(),
+            None,
+        )
+    }
+
+    /// Create a new `Country` from a `CountryPair` and Wikidata queries using `id`.
+    ///
+    /// Fetches the country's name in each of `arguments.languages`, its time zones if
+    /// `arguments.timezones` is set, and its calling code if `arguments.calling_codes` is set.
+    pub fn from_pair_and_id(
+        pair: &CountryPair,
+        id: impl AsRef<str>,
+        arguments: &Arguments,
+    ) -> Self {
         let name = pair.name.clone();
         let code = pair.code.clone();
         let coordinates = wikidata::query_for_coords_by_id(id.as_ref());
+        let names = wikidata::query_for_names_by_id(id.as_ref(), &arguments.languages);
+        let timezones = match arguments.timezones {
+            true => wikidata::query_for_timezones_by_id(id.as_ref()),
+            false => [].into(),
+        };
+        let calling_code = match arguments.calling_codes {
+            true => wikidata::query_for_calling_code_by_id(id.as_ref()),
+            false => None,
+        };
 
         Self {
             name,
             code,
             coordinates,
+            names,
+            timezones,
+            calling_code,
         }
     }
 
@@ -72,11 +135,16 @@ impl Country {
     ///
     /// ```rust
     /// assert_eq!(
-    ///     Country::new("EX", "Example", (1.0, 1.0)).as_rust_map_entry(0).as_ref(),
+    ///     Country::new("EX", "Example", (1.0, 1.0), HashMap::new(), [].into(), None)
+    ///         .as_rust_map_entry(0)
+    ///         .as_ref(),
     ///     r#"{let ex = Country {
     ///     name: "Example".into(),
     ///     code: "EX".into(),
     ///     coordinates: (1.0, -1.0),
+    ///     names: HashMap::new(),
+    ///     timezones: Box::new([]),
+    ///     calling_code: None,
     /// }; (ex.code.clone(), ex)},
     /// "#
     /// ])
@@ -99,6 +167,12 @@ impl Country {
         }
 
         let (code, name, coordinates) = self.contents_as_strings();
+        let names = self.names_as_string();
+        let timezones = self.timezones_as_string();
+        let calling_code = match &self.calling_code {
+            Some(calling_code) => format!("Some({calling_code:?}.into())"),
+            None => "None".to_string(),
+        };
         let code_lower = match self.code.as_ref() {
             "??" => "unknown",
             _ => &format!("c_{}", self.code.to_lowercase()),
@@ -109,12 +183,71 @@ impl Country {
     name: {name},
     code: {code},
     coordinates: {coordinates},
+    names: {names},
+    timezones: {timezones},
+    calling_code: {calling_code},
 }}; ({code_lower}.code.clone(), {code_lower})}},"#
         );
 
         indent_string(&output, indent)
     }
 
+    /// Returns `self.names` as a Rust expression creating a `HashMap`, sorted by language code.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// let names = HashMap::from([("fr".into(), "Exemple".into())]);
+    ///
+    /// assert_eq!(
+    ///     Country::new("EX", "Example", (1.0, 1.0), names, [].into(), None)
+    ///         .names_as_string()
+    ///         .as_ref(),
+    ///     r#"HashMap::from([("fr".into(), "Exemple".into())])"#
+    /// );
+    /// ```
+    fn names_as_string(&self) -> Box<str> {
+        if self.names.is_empty() {
+            return "HashMap::new()".into();
+        }
+
+        let mut names: Vec<_> = self.names.iter().collect();
+        names.sort_unstable();
+
+        let names = names
+            .into_iter()
+            .map(|(language, name)| format!("({language:?}.into(), {name:?}.into())"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("HashMap::from([{names}])").into_boxed_str()
+    }
+
+    /// Returns `self.timezones` as a Rust expression creating a boxed slice.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// let timezones = ["Europe/Brussels".into()].into();
+    ///
+    /// assert_eq!(
+    ///     Country::new("EX", "Example", (1.0, 1.0), HashMap::new(), timezones, None)
+    ///         .timezones_as_string()
+    ///         .as_ref(),
+    ///     r#"Box::new(["Europe/Brussels".into()])"#
+    /// );
+    /// ```
+    fn timezones_as_string(&self) -> Box<str> {
+        let timezones = self
+            .timezones
+            .iter()
+            .map(|timezone| format!("{timezone:?}.into()"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("Box::new([{timezones}])").into_boxed_str()
+    }
+
     /// Returns self as a tuple of four Strings holding string literals: `(code, name)`
     ///
     /// Example usage:
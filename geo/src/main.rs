@@ -18,6 +18,7 @@
 use std::{collections::HashMap, process::Command, str::FromStr};
 
 use chrono::{SecondsFormat, Utc};
+use clap::Parser;
 
 mod country;
 use country::{Country, CountryPair};
@@ -27,7 +28,37 @@ use error::Error;
 
 mod wikidata;
 
+/// Generates `ip_geo`'s list of countries from `location(8)` and Wikidata.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Arguments {
+    /// Also fetch country names in these languages from Wikidata.
+    ///
+    /// Ex. `--languages en,fr,de`.
+    #[arg(long = "languages", value_delimiter = ',')]
+    languages: Vec<String>,
+
+    /// Also fetch the IANA time zones of each country from Wikidata.
+    #[arg(long = "timezones")]
+    timezones: bool,
+
+    /// Also fetch the calling code of each country from Wikidata.
+    #[arg(long = "calling-codes")]
+    calling_codes: bool,
+
+    /// Skip Wikidata entirely, setting every country's coordinates to `(0.0, 0.0)`.
+    ///
+    /// Makes runs fast, reproducible, and free of network access.
+    #[arg(
+        long = "no-coordinates",
+        conflicts_with_all = ["languages", "timezones", "calling_codes"]
+    )]
+    no_coordinates: bool,
+}
+
 fn main() {
+    let arguments = Arguments::parse();
+
     // Tor's additions to the database from libloc
     let additional_countries = vec![CountryPair::new("??", "Unknown")];
 
@@ -41,7 +72,8 @@ fn main() {
         ("AP", "Q48"),
     ]);
 
-    let countries = get_country_list(additional_countries, nonstandard_countries).unwrap();
+    let countries =
+        get_country_list(additional_countries, nonstandard_countries, &arguments).unwrap();
 
     // dbg!(&countries);
     // print_country_list_as_code_and_name(&countries);
@@ -70,6 +102,7 @@ fn print_country_list_as_code_and_name(countries: &[Country]) {
 fn print_country_list_as_rust_hashmap(countries: &[Country], indent: u8) {
     let location_version = get_location_version().unwrap();
     let date_time = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true); // Ex. 2024-07-21T04:11:07Z
+    let count = countries.len();
 
     print!(
         r#"// This file was @generated by ip_geo/geo using {location_version} and Wikidata at {date_time}. Do not edit!
@@ -91,7 +124,10 @@ fn print_country_list_as_rust_hashmap(countries: &[Country], indent: u8) {
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use std::{{collections::HashMap, sync::Arc}};
+use std::{{
+    collections::{{HashMap, HashSet}},
+    sync::{{Arc, LazyLock}},
+}};
 
 /// Represents a country or other geographic region.
 #[derive(Clone, Debug)]
@@ -108,6 +144,152 @@ pub struct Country {{
     ///
     /// Ex. `(4.668055555, 50.641111111)`.
     pub coordinates: (f64, f64),
+    /// The names of the country in other languages, with the language code as the key.
+    ///
+    /// Ex. `"fr"` => `"Belgique"`.
+    pub names: HashMap<Box<str>, Box<str>>,
+    /// The IANA time zones that the country is located in.
+    ///
+    /// Ex. `["Europe/Brussels"]`.
+    pub timezones: Box<[Box<str>]>,
+    /// The country calling code, if it has one.
+    ///
+    /// Ex. `"+32"` for Belgium.
+    pub calling_code: Option<Box<str>>,
+}}
+
+/// When the country list was generated from Wikidata, as an RFC 3339 timestamp.
+///
+/// Ex. `"2024-08-05T21:32:19Z"`.
+pub const COUNTRY_LIST_GENERATED_AT: &str = "{date_time}";
+
+/// The number of countries in `get_countries`.
+pub const COUNTRY_LIST_COUNT: usize = {count};
+
+/// Returns when the country list was generated and how many countries it holds, ex.
+/// `("2024-08-05T21:32:19Z", 255)`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::country_list::{{get_countries, provenance}};
+///
+/// let (generated_at, count) = provenance();
+///
+/// assert!(generated_at.ends_with('Z'));
+/// assert_eq!(count, get_countries().len());
+/// ```
+pub fn provenance() -> (&'static str, usize) {{
+    (COUNTRY_LIST_GENERATED_AT, COUNTRY_LIST_COUNT)
+}}
+
+/// Every country in `get_countries`, sorted by the ISO 3166-1 alpha-2 code.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::country_list::all_countries;
+///
+/// let codes: Vec<_> = all_countries().map(|country| country.code.as_ref()).collect();
+///
+/// assert!(codes.contains(&"BE"));
+/// assert!(codes.is_sorted());
+/// ```
+pub fn all_countries() -> impl Iterator<Item = &'static Country> {{
+    static COUNTRIES: LazyLock<Vec<Country>> = LazyLock::new(|| {{
+        let mut countries: Vec<Country> = get_countries().into_values().collect();
+        countries.sort_unstable_by(|a, b| a.code.cmp(&b.code));
+
+        countries
+    }});
+
+    COUNTRIES.iter()
+}}
+
+/// Every country with a given calling code, sorted by the ISO 3166-1 alpha-2 code.
+///
+/// The leading `+` is optional, ex. `"+1"` and `"1"` are equivalent.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::country_list::by_calling_code;
+///
+/// assert!(by_calling_code("+999").is_empty());
+/// ```
+pub fn by_calling_code(code: &str) -> Vec<Country> {{
+    let code = code.trim_start_matches('+');
+
+    all_countries()
+        .filter(|country| {{
+            country
+                .calling_code
+                .as_deref()
+                .is_some_and(|calling_code| calling_code.trim_start_matches('+') == code)
+        }})
+        .cloned()
+        .collect()
+}}
+
+/// The ISO 3166-1 alpha-2 codes of the member states of the European Union.
+///
+/// Curated by hand, as of 2024, rather than generated from Wikidata. Greece is listed as `"GR"`,
+/// not the EU's own `"EL"`, and the libloc `"EU"` region is not itself a member state.
+pub const EU_MEMBERS: [&str; 27] = [
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+/// The set of `EU_MEMBERS`, for membership checks.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::country_list::eu_members;
+///
+/// assert!(eu_members().contains("BE"));
+/// assert!(!eu_members().contains("CH"));
+/// assert_eq!(eu_members().len(), 27);
+/// ```
+pub fn eu_members() -> &'static HashSet<&'static str> {{
+    static MEMBERS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| HashSet::from(EU_MEMBERS));
+
+    &MEMBERS
+}}
+
+/// The country whose stored coordinates are closest to the given coordinates, by great-circle
+/// distance.
+///
+/// This is approximate, as it compares against the center of each country rather than its borders.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::country_list::nearest_country;
+///
+/// // Brussels
+/// assert_eq!(nearest_country(50.8467, 4.3525).code, "BE".into());
+/// ```
+pub fn nearest_country(latitude: f64, longitude: f64) -> &'static Country {{
+    /// The haversine of the central angle between two points given as `(longitude, latitude)` in
+    /// degrees, which increases with the distance between them.
+    fn haversine((lon_a, lat_a): (f64, f64), (lon_b, lat_b): (f64, f64)) -> f64 {{
+        let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+        let delta_lat = lat_b - lat_a;
+        let delta_lon = (lon_b - lon_a).to_radians();
+
+        (delta_lat / 2.0).sin().powi(2)
+            + lat_a.cos() * lat_b.cos() * (delta_lon / 2.0).sin().powi(2)
+    }}
+
+    let point = (longitude, latitude);
+    let distance = |country: &Country| haversine(point, country.coordinates);
+
+    all_countries()
+        // Has no location
+        .filter(|country| country.code.as_ref() != "??")
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .expect("a country with a location")
 }}
 
 /// A map of countries, with the ISO 3166-1 alpha-2 code as the key.
@@ -142,9 +324,13 @@ pub fn get_countries() -> HashMap<Arc<str>, Country> {{HashMap::from([
 ///
 /// `nonstandard_countries` represent a libloc country code and a Wikidata ID, where the code
 /// deviates from ISO 3166-1 alpha-2.
+///
+/// `arguments` selects which additional data (localized names, time zones, calling codes) to
+/// fetch, or whether to skip Wikidata entirely.
 fn get_country_list(
     mut additional_countries: Vec<CountryPair>,
     nonstandard_countries: HashMap<&str, &str>,
+    arguments: &Arguments,
 ) -> Result<Box<[Country]>, Error> {
     let input = call("location list-countries --show-name")?;
     let mut countries = Vec::with_capacity(input.len() + additional_countries.len());
@@ -169,16 +355,17 @@ fn get_country_list(
 
     // For a given `CountryPair`, create a `Country` from it using the appropriate method.
     let from_pair = move |pair: &CountryPair| match pair.code.as_ref() {
-        // The pair has no associated country
-        "??" => Country::new(&pair.code, &pair.name, (0.0, 0.0)),
+        // The pair has no associated country, or Wikidata should not be queried
+        "??" => Country::from_pair_offline(pair),
+        _ if arguments.no_coordinates => Country::from_pair_offline(pair),
 
         // The pair is a real country or other geographic area
         _ => match nonstandard_countries.get(pair.code.as_ref()) {
             // The pair cannot be identified on Wikidata from its code, and must use a hardcoded ID
-            Some(id) => Country::from_pair_and_id(pair, id),
+            Some(id) => Country::from_pair_and_id(pair, id, arguments),
 
             // The pair can be identified on Wikidata from its code
-            None => Country::from_pair(pair),
+            None => Country::from_pair(pair, arguments),
         },
     };
 
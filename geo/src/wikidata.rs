@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use crate::Error;
 use mediawiki::ApiSync;
@@ -97,6 +97,189 @@ WHERE {{
     parse_coords(point).ok_or(Error::InvalidPoint).unwrap()
 }
 
+/// Query Wikidata for a country's name in each of `languages`, based on a Wikidata ID.
+///
+/// Returns a map with the language code as the key. Languages without a name are omitted.
+pub fn query_for_names_by_id(id: &str, languages: &[String]) -> HashMap<Box<str>, Box<str>> {
+    if languages.is_empty() {
+        return HashMap::new();
+    }
+
+    let query = format!(
+        r#"
+SELECT DISTINCT
+  ?label                        # Ex. Belgique
+  (LANG(?label) AS ?language)   # Ex. fr
+WHERE {{
+  wd:{id} rdfs:label ?label. # Get its names
+  FILTER(LANG(?label) IN ({languages}))
+}}
+"#,
+        languages = as_sparql_list(languages)
+    );
+
+    parse_names(&wikidata_query(&query).expect("the result of a Wikidata query"))
+}
+
+/// Query Wikidata for a country's name in each of `languages`, based on a two-letter code.
+///
+/// Returns a map with the language code as the key. Languages without a name are omitted.
+pub fn query_for_names_by_code(code: &str, languages: &[String]) -> HashMap<Box<str>, Box<str>> {
+    if languages.is_empty() {
+        return HashMap::new();
+    }
+
+    let query = format!(
+        r#"
+SELECT DISTINCT
+  ?label                        # Ex. Belgique
+  (LANG(?label) AS ?language)   # Ex. fr
+WHERE {{
+  VALUES ?inputCode {{
+    """{code}""" # Ex. BE
+  }}
+
+  ?item p:P297 ?code.       # Get items with country codes
+  ?code ps:P297 ?inputCode. # Match country code against `?inputCode`
+  ?item rdfs:label ?label.  # Get its names
+  FILTER(LANG(?label) IN ({languages}))
+}}
+"#,
+        languages = as_sparql_list(languages)
+    );
+
+    parse_names(&wikidata_query(&query).expect("the result of a Wikidata query"))
+}
+
+/// Query Wikidata for the IANA time zones a country is located in, based on a Wikidata ID.
+///
+/// Returns the time zones sorted and deduplicated.
+pub fn query_for_timezones_by_id(id: &str) -> Box<[Box<str>]> {
+    let query = format!(
+        r#"
+SELECT DISTINCT
+  ?timezone # Ex. Europe/Brussels
+WHERE {{
+  wd:{id} wdt:P421 ?zone.     # Get the time zones it is located in
+  ?zone wdt:P6687 ?timezone. # Get their IANA time zone IDs
+}}
+"#
+    );
+
+    parse_timezones(&wikidata_query(&query).expect("the result of a Wikidata query"))
+}
+
+/// Query Wikidata for the IANA time zones a country is located in, based on a two-letter code.
+///
+/// Returns the time zones sorted and deduplicated.
+pub fn query_for_timezones_by_code(code: &str) -> Box<[Box<str>]> {
+    let query = format!(
+        r#"
+SELECT DISTINCT
+  ?timezone # Ex. Europe/Brussels
+WHERE {{
+  VALUES ?inputCode {{
+    """{code}""" # Ex. BE
+  }}
+
+  ?item p:P297 ?code.        # Get items with country codes
+  ?code ps:P297 ?inputCode.  # Match country code against `?inputCode`
+  ?item wdt:P421 ?zone.      # Get the time zones it is located in
+  ?zone wdt:P6687 ?timezone. # Get their IANA time zone IDs
+}}
+"#
+    );
+
+    parse_timezones(&wikidata_query(&query).expect("the result of a Wikidata query"))
+}
+
+/// Collect the `?timezone` bindings of a query into a sorted and deduplicated list.
+fn parse_timezones(results: &[Value]) -> Box<[Box<str>]> {
+    let mut timezones: Vec<Box<str>> = results
+        .iter()
+        .filter_map(|result| get_str_value(result, "timezone").ok())
+        .map(Into::into)
+        .collect();
+
+    timezones.sort_unstable();
+    timezones.dedup();
+
+    timezones.into_boxed_slice()
+}
+
+/// Query Wikidata for a country's calling code, based on a Wikidata ID.
+///
+/// If Wikidata lists multiple calling codes, returns the first in sorted order.
+pub fn query_for_calling_code_by_id(id: &str) -> Option<Box<str>> {
+    let query = format!(
+        r#"
+SELECT DISTINCT
+  ?callingCode # Ex. +32
+WHERE {{
+  wd:{id} wdt:P474 ?callingCode. # Get its calling code
+}}
+ORDER BY ?callingCode
+"#
+    );
+
+    parse_calling_code(&wikidata_query(&query).expect("the result of a Wikidata query"))
+}
+
+/// Query Wikidata for a country's calling code, based on a two-letter code.
+///
+/// If Wikidata lists multiple calling codes, returns the first in sorted order.
+pub fn query_for_calling_code_by_code(code: &str) -> Option<Box<str>> {
+    let query = format!(
+        r#"
+SELECT DISTINCT
+  ?callingCode # Ex. +32
+WHERE {{
+  VALUES ?inputCode {{
+    """{code}""" # Ex. BE
+  }}
+
+  ?item p:P297 ?code.          # Get items with country codes
+  ?code ps:P297 ?inputCode.    # Match country code against `?inputCode`
+  ?item wdt:P474 ?callingCode. # Get its calling code
+}}
+ORDER BY ?callingCode
+"#
+    );
+
+    parse_calling_code(&wikidata_query(&query).expect("the result of a Wikidata query"))
+}
+
+/// Get the first `?callingCode` binding of a query.
+fn parse_calling_code(results: &[Value]) -> Option<Box<str>> {
+    let result = results.first()?;
+
+    get_str_value(result, "callingCode").ok().map(Into::into)
+}
+
+/// Format language codes as the contents of a SPARQL list of strings.
+///
+/// Ex. `["en", "fr"]` -> `"en", "fr"`.
+fn as_sparql_list(languages: &[String]) -> String {
+    languages
+        .iter()
+        .map(|language| format!("\"{}\"", language.trim()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Collect the `?label` and `?language` bindings of a query into a map of language codes to names.
+fn parse_names(results: &[Value]) -> HashMap<Box<str>, Box<str>> {
+    results
+        .iter()
+        .filter_map(|result| {
+            let language = get_str_value(result, "language").ok()?;
+            let label = get_str_value(result, "label").ok()?;
+
+            Some((language.into(), label.into()))
+        })
+        .collect()
+}
+
 /// Get the internal string value of a given field that holds a string in a Serde JSON value.
 fn get_str_value<'st>(result: &'st Value, label: &str) -> Result<&'st str, Error> {
     get_value(result, label)?
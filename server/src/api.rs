@@ -15,87 +15,639 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use std::net::{Ipv4Addr, Ipv6Addr};
+#[cfg(unix)]
+use std::fs;
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
 
-use ip_geo::{country_list::Country, IpAddrMap};
+use ip_geo::country_list::all_countries;
 use serde::Serialize;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpSocket},
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
+    task::JoinSet,
+    time::{Instant, Sleep},
+};
+use tokio_stream::wrappers::ReceiverStream;
 use warp::{
-    http::StatusCode,
-    reply::{json, with_status, Json, WithStatus},
+    filters::BoxedFilter,
+    http::{
+        header::{CONTENT_TYPE, ETAG},
+        HeaderValue, StatusCode,
+    },
+    reject::Reject,
+    reply::{with_header, with_status, Response},
     Filter, Rejection, Reply,
 };
 
-use crate::parse::Maps;
+use crate::{
+    error::Error,
+    lookup::{self, AllowQuery, ApiError, ApiVersion, Cache, Coordinates, ReplyFormat},
+    parse::Maps,
+};
 
-pub static API_VERSION: &str = "v0";
+/// The sockets to serve the API on.
+pub enum Listeners {
+    /// TCP sockets, ex. `127.0.0.1:26000`.
+    Tcp(Vec<TcpListener>),
+    /// A Unix domain socket, and the path of its file.
+    #[cfg(unix)]
+    Unix(UnixListener, Box<Path>),
+}
 
-/// For a give Warp routes map, and a list of target `SocketAddr`s, print the targets and serve the
-/// routes on them.
-macro_rules! serve {
-    ( $routes:expr, $( $target:expr ),+ ) => {
-        ::tokio::join!(
-            $({
-                println!("Serving on http://{}/{}/", $target, $crate::api::API_VERSION);
-                ::warp::serve($routes.clone()).run($target)
-            }),+
-        );
+/// Limits on the connections served by `serve`.
+///
+/// The default places no limits, keeping every connection open until the client closes it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConnectionLimits {
+    /// The most connections to serve at once, or `None` for no limit.
+    ///
+    /// Once reached, further connections wait to be accepted until one closes.
+    pub max_connections: Option<usize>,
+    /// How long a connection may go without reading or writing before it is closed, or `None` to
+    /// keep idle connections open.
+    pub idle_timeout: Option<Duration>,
+}
+
+/// How `bind` retries binding a TCP socket whose address is in use.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BindRetry {
+    /// The most times to retry binding each target, or zero to fail immediately.
+    pub attempts: u32,
+    /// How long to wait before the first retry, doubling after each retry.
+    pub delay: Duration,
+}
+
+/// Bind a listener to each of `targets`, before any are served, each queueing up to `backlog`
+/// connections waiting to be accepted.
+///
+/// While a target is in use, ex. by a previous instance of the server during a rolling restart,
+/// binding it is retried with exponential backoff according to `retry`.
+///
+/// Returns `Error::Bind` naming the first target that could not be bound, ex. because it is in use
+/// by another program, or by an earlier target on the same port.
+pub async fn bind(
+    targets: impl IntoIterator<Item = SocketAddr>,
+    backlog: u32,
+    retry: BindRetry,
+) -> Result<Listeners, Error> {
+    let mut listeners = Vec::new();
+
+    for target in targets {
+        let listener = bind_tcp_with_retry(target, backlog, retry)
+            .await
+            .map_err(|source| Error::Bind { target, source })?;
+
+        listeners.push(listener);
+    }
+
+    Ok(Listeners::Tcp(listeners))
+}
+
+/// `bind_tcp`, retrying according to `retry` while `target` is in use.
+async fn bind_tcp_with_retry(
+    target: SocketAddr,
+    backlog: u32,
+    retry: BindRetry,
+) -> io::Result<TcpListener> {
+    let mut delay = retry.delay;
+
+    for attempt in 1..=retry.attempts {
+        match bind_tcp(target, backlog) {
+            Err(error) if error.kind() == io::ErrorKind::AddrInUse => {
+                log::warn!(
+                    "Failed to bind to {target}, retrying in {delay:?} ({attempt}/{}): {error}",
+                    retry.attempts
+                );
+
+                tokio::time::sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+
+    bind_tcp(target, backlog)
+}
+
+/// Bind a TCP listener to `target`, like `TcpListener::bind`, but with a given `backlog`.
+fn bind_tcp(target: SocketAddr, backlog: u32) -> io::Result<TcpListener> {
+    let socket = match target {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
     };
+
+    // Matches `TcpListener::bind`, allowing a restarted server to rebind while old connections
+    // linger in `TIME_WAIT`
+    #[cfg(not(windows))]
+    socket.set_reuseaddr(true)?;
+
+    socket.bind(target)?;
+    socket.listen(backlog)
+}
+
+/// Bind a listener to a Unix domain socket, creating its file at `path`.
+///
+/// Returns `Error::BindUnix` if it could not be bound, ex. because a file already exists at `path`.
+/// On platforms other than Unix, always returns `Error::UnixSocketUnsupported`.
+pub fn bind_unix(path: Box<Path>) -> Result<Listeners, Error> {
+    #[cfg(unix)]
+    return match UnixListener::bind(&path) {
+        Ok(listener) => Ok(Listeners::Unix(listener, path)),
+        Err(source) => Err(Error::BindUnix { path, source }),
+    };
+
+    #[cfg(not(unix))]
+    return Err(Error::UnixSocketUnsupported(path));
 }
 
-pub fn get_routes(maps: Maps) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    let search_ipv4 = move |ipv4_addr: Ipv4Addr| search_clean_ip_map(ipv4_addr, &maps.v4);
-    let search_ipv6 = move |ipv6_addr: Ipv6Addr| search_clean_ip_map(ipv6_addr, &maps.v6);
+/// Serve Warp `routes` on each of `listeners` until `shutdown_signal()` resolves, letting in-flight
+/// requests finish.
+///
+/// Connections are limited according to `limits`. A Unix domain socket's file is removed once it is
+/// shut down.
+pub async fn serve(
+    routes: BoxedFilter<(Response,)>,
+    listeners: Listeners,
+    limits: ConnectionLimits,
+) {
+    let semaphore = limits
+        .max_connections
+        .map(|max_connections| Arc::new(Semaphore::new(max_connections)));
 
-    let ipv4 = warp::path!("ipv4" / Ipv4Addr).map(search_ipv4);
-    let ipv6 = warp::path!("ipv6" / Ipv6Addr).map(search_ipv6);
+    let listeners = match listeners {
+        Listeners::Tcp(listeners) => listeners,
+        #[cfg(unix)]
+        Listeners::Unix(listener, path) => {
+            log::info!("Serving on {}", path.display());
 
-    warp::get().and(warp::path(API_VERSION)).and(ipv4.or(ipv6))
+            let listener = Arc::new(listener);
+            let accept = move || {
+                let listener = listener.clone();
+                async move { listener.accept().await.map(|(stream, _)| stream) }
+            };
+
+            warp::serve(routes)
+                .serve_incoming_with_graceful_shutdown(
+                    incoming(accept, semaphore, limits.idle_timeout),
+                    shutdown_signal(),
+                )
+                .await;
+
+            if let Err(error) = fs::remove_file(&path) {
+                log::warn!("Failed to remove {}: {error}", path.display());
+            }
+
+            return;
+        }
+    };
+
+    let mut servers = JoinSet::new();
+
+    for listener in listeners {
+        if let Ok(address) = listener.local_addr() {
+            log::info!("Serving on http://{address}/");
+        }
+
+        let listener = Arc::new(listener);
+        let accept = move || {
+            let listener = listener.clone();
+            async move { listener.accept().await.map(|(stream, _)| stream) }
+        };
+
+        servers.spawn(
+            warp::serve(routes.clone()).serve_incoming_with_graceful_shutdown(
+                incoming(accept, semaphore.clone(), limits.idle_timeout),
+                shutdown_signal(),
+            ),
+        );
+    }
+
+    while servers.join_next().await.is_some() {}
 }
 
-/// Search an IPv4 address map for an IP address.
+/// Accept connections with `accept` on a separate task, returning them as a stream.
 ///
-/// Assumes that the `IpAddrMap` is clean, otherwise it return an internal server error (code 500).
-fn search_clean_ip_map<A: Ord + Copy>(ip_addr: A, ip_map: &IpAddrMap<A, Country>) -> impl Reply {
-    fn success(country: &Country) -> WithStatus<Json> {
-        json_with_status(country, StatusCode::OK)
+/// Errors from `accept` are logged rather than returned, so that one failed accept doesn't stop the
+/// server. After an error like too many open files, waits `ACCEPT_ERROR_DELAY` before retrying.
+///
+/// If `semaphore` is set, waits for a permit before accepting each connection, which is held until
+/// the connection closes. Each connection is closed once idle for `idle_timeout`, if set.
+fn incoming<S, F, A>(
+    mut accept: A,
+    semaphore: Option<Arc<Semaphore>>,
+    idle_timeout: Option<Duration>,
+) -> ReceiverStream<io::Result<Connection<S>>>
+where
+    S: Send + 'static,
+    F: Future<Output = io::Result<S>> + Send + 'static,
+    A: FnMut() -> F + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(1);
+
+    tokio::spawn(async move {
+        loop {
+            let permit = match &semaphore {
+                Some(semaphore) => match semaphore.clone().acquire_owned().await {
+                    Ok(permit) => Some(permit),
+                    // The semaphore is never closed
+                    Err(_) => break,
+                },
+                None => None,
+            };
+
+            let stream = match accept().await {
+                Ok(stream) => stream,
+                // Like Hyper's `AddrIncoming`, keep accepting after an error instead of sending it
+                // to the server, which would stop it
+                Err(error) if is_connection_error(&error) => {
+                    log::debug!("Failed to accept a connection: {error}");
+                    continue;
+                }
+                Err(error) => {
+                    // Ex. too many open files, which only resolves once other connections close
+                    log::error!("Failed to accept a connection: {error}");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+
+            let connection = Connection::new(stream, permit, idle_timeout);
+
+            // The server has shut down
+            if sender.send(Ok(connection)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    ReceiverStream::new(receiver)
+}
+
+/// How long `incoming` waits before accepting again after an error that is not specific to one
+/// connection.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+/// Returns true if an error from accepting a connection only affects that connection, ex. because
+/// the client reset it before it was accepted.
+fn is_connection_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// A connection accepted by `incoming`, holding its permit under `ConnectionLimits::max_connections`
+/// until it is closed, and failing once idle for `ConnectionLimits::idle_timeout`.
+struct Connection<S> {
+    stream: S,
+    _permit: Option<OwnedSemaphorePermit>,
+    /// The idle timeout, and a timer that fires once the connection has been idle for it.
+    idle: Option<(Duration, Pin<Box<Sleep>>)>,
+}
+
+impl<S> Connection<S> {
+    fn new(
+        stream: S,
+        permit: Option<OwnedSemaphorePermit>,
+        idle_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            stream,
+            _permit: permit,
+            idle: idle_timeout.map(|timeout| (timeout, Box::pin(tokio::time::sleep(timeout)))),
+        }
     }
 
-    fn error(error: ip_geo::Error) -> WithStatus<Json> {
-        match error {
-            ip_geo::Error::NoValueFound => json_str_error(
-                "no country associated with IP address",
-                StatusCode::NOT_FOUND,
-            ),
-            _ => {
-                eprintln!("Error 500: request resulted in error: '{error}'");
-                json_str_error(&error.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+    /// Restart the idle timer if an I/O operation completed, or return a timeout error if it is
+    /// still pending and the connection has been idle for too long.
+    fn check_idle<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let Some((timeout, timer)) = &mut self.idle else {
+            return poll;
+        };
+
+        match poll {
+            Poll::Ready(result) => {
+                timer.as_mut().reset(Instant::now() + *timeout);
+
+                Poll::Ready(result)
             }
+            Poll::Pending => match timer.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Err(io::ErrorKind::TimedOut.into())),
+                Poll::Pending => Poll::Pending,
+            },
         }
     }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Connection<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.stream).poll_read(cx, buf);
+
+        self.check_idle(cx, poll)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Connection<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.stream).poll_write(cx, buf);
+
+        self.check_idle(cx, poll)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.stream).poll_flush(cx);
+
+        self.check_idle(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Wait for a request to shut down the server: Ctrl+C, or SIGTERM on Unix.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("a Ctrl+C signal handler");
+    };
 
-    match ip_map.try_search(ip_addr) {
-        Ok(country) => success(country),
-        Err(err) => error(err),
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("a SIGTERM signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
 }
 
-/// Returns a JSON reply with a given status.
+/// Construct the API's routes, searching the given `Maps` and caching results in `cache`.
+///
+/// IPv4-mapped and IPv4-compatible IPv6 addresses (ex. `::ffff:1.2.3.4`) are resolved against the
+/// IPv4 map, as if the IPv4 address were queried directly.
+///
+/// `countries` lists every known country, sorted by country code, and `nearest?lat=..&lon=..`
+/// returns the country whose center is closest to the given coordinates.
+///
+/// `allow/{address}?countries=US,CA` returns whether an IP address of either family is associated
+/// with one of the given countries, and `allow/{address}?region=eu` whether it is associated with a
+/// member state of the European Union.
+///
+/// `info` returns the paths, modification times, and entry counts of the loaded databases, and a
+/// hash of their contents.
+///
+/// If `enable_dns` is true, `host/{hostname}` resolves a hostname with DNS and returns the country
+/// of each IP address it resolved to.
+///
+/// Successful `ipv4` and `ipv6` lookups carry an `ETag`, and requests with a matching
+/// `If-None-Match` header get not modified (code 304) instead of the body.
 ///
-/// Returns JSON in the format of:
+/// If `cors_origins` is not empty, browsers on those origins (or any origin, if it holds `*`) may
+/// call the API. Otherwise, no CORS headers are sent.
 ///
-/// ```json
-/// {"error":"example error text"}
-/// ```
-fn json_str_error(error: &str, code: StatusCode) -> WithStatus<Json> {
-    #[derive(Serialize)]
-    struct SerializableError<'s> {
-        error: &'s str,
+/// Every `ApiVersion` is served from the same maps, under its own path prefix, ex. `/v0/` and
+/// `/v1/`. Response bodies are JSON, formatted according to `ApiVersion::format`.
+///
+/// # Panics
+///
+/// Panics if a CORS origin is not a valid origin, ex. `https://example.com`.
+pub fn get_routes(
+    maps: Maps,
+    cache: Cache,
+    cors_origins: &[String],
+    enable_dns: bool,
+    format: ReplyFormat,
+) -> BoxedFilter<(Response,)> {
+    let [v0, v1] = ApiVersion::ALL.map(|version| {
+        let maps = maps.clone();
+        let cache = cache.clone();
+
+        version_routes(version, maps, cache, enable_dns, version.format(format))
+    });
+    let routes = v0.or(v1).unify();
+
+    if cors_origins.is_empty() {
+        return routes.boxed();
     }
 
-    json_with_status(&SerializableError { error }, code)
+    let cors = warp::cors()
+        .allow_method("GET")
+        .allow_header("if-none-match")
+        .expose_header("etag");
+    let cors = if cors_origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(cors_origins.iter().map(String::as_str))
+    };
+
+    routes.with(cors).map(Reply::into_response).boxed()
 }
 
-/// Returns a JSON reply with the given contents and status code.
-fn json_with_status(contents: &impl Serialize, code: StatusCode) -> WithStatus<Json> {
-    with_status(json(contents), code)
+/// Construct the routes of one `version` of the API, under its path prefix, as described by
+/// `get_routes`.
+fn version_routes(
+    version: ApiVersion,
+    maps: Maps,
+    cache: Cache,
+    enable_dns: bool,
+    format: ReplyFormat,
+) -> BoxedFilter<(Response,)> {
+    let search_host = {
+        let maps = maps.clone();
+        let cache = cache.clone();
+        move |host: String| {
+            let maps = maps.clone();
+            let cache = cache.clone();
+            async move {
+                if !enable_dns {
+                    return Err(warp::reject::not_found());
+                }
+
+                Ok(reply(
+                    format,
+                    lookup::search_host(&host, &maps, &cache).await,
+                ))
+            }
+        }
+    };
+    let search_ip = {
+        let maps = maps.clone();
+        move |ip_addr: IpAddr, if_none_match: Option<String>| {
+            with_etag(ip_addr, if_none_match, maps.version, format, || {
+                let lookup = lookup::search_ip(ip_addr, &maps, &cache);
+
+                reply(format, lookup.map(|lookup| lookup.for_version(version)))
+            })
+        }
+    };
+    let search_ipv4 = {
+        let search_ip = search_ip.clone();
+        move |ipv4_addr: Ipv4Addr, if_none_match| search_ip(ipv4_addr.into(), if_none_match)
+    };
+    let search_ipv6 =
+        move |ipv6_addr: Ipv6Addr, if_none_match| search_ip(ipv6_addr.into(), if_none_match);
+    let info = {
+        let info = maps.info.clone();
+        move || reply(format, Ok::<_, ApiError>(&*info))
+    };
+    let search_allowed = move |ip_addr: IpAddr, query: AllowQuery| {
+        reply(format, lookup::search_allowed(ip_addr, &query, &maps))
+    };
+
+    let if_none_match = warp::header::optional::<String>("if-none-match");
+    let ipv4 = warp::path("ipv4")
+        .and(ip_param::<Ipv4Addr>())
+        .and(warp::path::end())
+        .and(if_none_match)
+        .map(search_ipv4);
+    let ipv6 = warp::path("ipv6")
+        .and(ip_param::<Ipv6Addr>())
+        .and(warp::path::end())
+        .and(if_none_match)
+        .map(search_ipv6);
+    let countries = warp::path!("countries").map(move || {
+        reply(
+            format,
+            Ok::<_, ApiError>(all_countries().collect::<Vec<_>>()),
+        )
+    });
+    let nearest = warp::path!("nearest")
+        .and(warp::query::<Coordinates>())
+        .map(move |coordinates: Coordinates| {
+            reply(format, lookup::search_nearest_country(&coordinates))
+        });
+    let allow = warp::path("allow")
+        .and(ip_param::<IpAddr>())
+        .and(warp::path::end())
+        .and(warp::query::<AllowQuery>())
+        .map(search_allowed);
+    let info = warp::path!("info").map(info);
+    let host = warp::path!("host" / String).and_then(search_host);
+
+    let routes = warp::get()
+        .and(warp::path(version.path()))
+        .and(
+            ipv4.or(ipv6)
+                .or(countries)
+                .or(nearest)
+                .or(allow)
+                .or(info)
+                .or(host),
+        )
+        .recover(move |rejection| recover_invalid_ip(format, rejection));
+
+    routes.map(Reply::into_response).boxed()
+}
+
+/// An IP address in the path that could not be parsed.
+#[derive(Debug)]
+struct InvalidIp(String);
+
+impl Reject for InvalidIp {}
+
+/// Extract an IP address from the next segment of the path, rejecting with `InvalidIp` if it can't
+/// be parsed, rather than with not found as `warp::path::param` does.
+fn ip_param<A: FromStr + Send + 'static>() -> impl Filter<Extract = (A,), Error = Rejection> + Copy
+{
+    warp::path::param::<String>().and_then(|segment: String| async move {
+        segment
+            .parse::<A>()
+            .map_err(|_| warp::reject::custom(InvalidIp(segment)))
+    })
+}
+
+/// Reply to an `InvalidIp` rejection with a bad request (code 400), leaving any other rejection to
+/// Warp.
+async fn recover_invalid_ip(
+    format: ReplyFormat,
+    rejection: Rejection,
+) -> Result<Response, Rejection> {
+    match rejection.find::<InvalidIp>() {
+        Some(InvalidIp(segment)) => {
+            let error = ApiError::invalid_ip(&format!("invalid IP address '{segment}'"));
+
+            Ok(reply(format, Err::<(), _>(error)))
+        }
+        None => Err(rejection),
+    }
+}
+
+/// Run `search` for an IP address, attaching an `ETag` to a successful reply, unless
+/// `if_none_match` already matches it, in which case returns not modified (code 304) instead.
+///
+/// The search always runs first, so that `If-None-Match: *` only matches an address that has a
+/// country, as a failed search has no representation to match.
+fn with_etag(
+    ip_addr: IpAddr,
+    if_none_match: Option<String>,
+    version: u64,
+    format: ReplyFormat,
+    search: impl FnOnce() -> Response,
+) -> Response {
+    let response = search();
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let etag = lookup::etag(version, format, ip_addr);
+
+    let mut response = match if_none_match {
+        Some(tags) if lookup::etag_matches(&tags, &etag) => {
+            StatusCode::NOT_MODIFIED.into_response()
+        }
+        _ => response,
+    };
+
+    let etag = HeaderValue::from_str(&etag).expect("a hexadecimal ETag to be a valid header");
+    response.headers_mut().insert(ETAG, etag);
+
+    response
+}
+
+/// Returns the result of a lookup as a JSON reply formatted according to `format`, with the status
+/// code of the error if it failed.
+fn reply(format: ReplyFormat, result: Result<impl Serialize, ApiError>) -> Response {
+    let (status, body) = format.to_json(result);
+
+    with_status(
+        with_header(body, CONTENT_TYPE, "application/json"),
+        StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+    )
+    .into_response()
 }
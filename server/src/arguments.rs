@@ -18,23 +18,85 @@
 
 use std::{
     fs,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     path::Path,
 };
 
-use clap::Parser;
-use serde::Deserialize;
+use clap::{Parser, ValueEnum};
+use ip_geo::parse::discover_database_paths;
+use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
 
+/// The HTTP frameworks the server can be run with.
+///
+/// Axum is only available with the `axum` feature.
+#[derive(ValueEnum, Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Framework {
+    Warp,
+    #[cfg(feature = "axum")]
+    Axum,
+}
+
 /// Represents the command-line arguments of the program.
-#[derive(Parser, Deserialize, Debug)]
+///
+/// Serializes to the format of the configuration file.
+#[derive(Parser, Deserialize, Serialize, Debug)]
 #[command(about, version, long_about = None)]
 pub struct Arguments {
     #[arg(short = 'f', long = "config-path")]
     #[serde(skip, default)]
     pub config_path: Option<Box<Path>>,
 
+    /// Serve on a single socket instead of `ipv4_pair` and `ipv6_pair`.
+    ///
+    /// Binding to `[::]` serves both IPv4 and IPv6 clients, where the OS supports dual-stack
+    /// sockets.
+    #[arg(short = 'b', long = "bind")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bind: Option<SocketAddr>,
+
+    /// Serve on a Unix domain socket, creating it at this path, instead of on TCP sockets.
+    ///
+    /// The socket is removed when the server shuts down.
+    #[arg(long = "unix-socket")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub unix_socket: Option<Box<Path>>,
+
+    /// The most connections waiting to be accepted on each TCP socket before new ones are refused.
+    #[arg(long = "listen-backlog")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub listen_backlog: Option<u32>,
+
+    /// How many times to retry binding each TCP socket while its address is in use, ex. by a
+    /// previous instance during a rolling restart, before giving up.
+    #[arg(long = "bind-retry")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bind_retry: Option<u32>,
+
+    /// How many milliseconds to wait before the first retry of `--bind-retry`, doubling after each
+    /// retry.
+    #[arg(long = "bind-retry-delay")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bind_retry_delay: Option<u64>,
+
+    /// The most connections to serve at once. Further connections wait to be accepted until one
+    /// closes.
+    ///
+    /// If not given, connections are not limited. Only supported with Warp.
+    #[arg(long = "max-connections")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_connections: Option<usize>,
+
+    /// Close connections that neither send nor receive anything for this many seconds, including
+    /// idle keep-alive connections.
+    ///
+    /// If not given, idle connections are kept open. Only supported with Warp.
+    #[arg(long = "idle-timeout")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub idle_timeout: Option<u64>,
+
     #[arg(short = '4', long = "ipv4")]
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv4_pair: Option<SocketAddrV4>,
@@ -43,6 +105,12 @@ pub struct Arguments {
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv4_db_path: Option<Box<Path>>,
 
+    /// Parse every file matching this glob pattern (ex. `geoip.d/*.csv`), as shards of one IPv4
+    /// database, instead of `--ipv4-db-path`.
+    #[arg(long = "ipv4-db-glob", conflicts_with = "ipv4_db_path")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ipv4_db_glob: Option<Box<str>>,
+
     #[arg(long = "ipv4-db-length")]
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv4_db_len: Option<usize>,
@@ -59,6 +127,12 @@ pub struct Arguments {
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv6_db_path: Option<Box<Path>>,
 
+    /// Parse every file matching this glob pattern (ex. `geoip.d/*.csv`), as shards of one IPv6
+    /// database, instead of `--ipv6-db-path`.
+    #[arg(long = "ipv6-db-glob", conflicts_with = "ipv6_db_path")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ipv6_db_glob: Option<Box<str>>,
+
     #[arg(long = "ipv6-db-length")]
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv6_db_len: Option<usize>,
@@ -66,6 +140,84 @@ pub struct Arguments {
     #[arg(long = "ipv6-db-comment")]
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub ipv6_db_comment: Option<char>,
+
+    /// Keep database ranges with the `??` placeholder country code, returning the unknown country
+    /// for them rather than not found.
+    #[arg(long = "keep-unknown", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub keep_unknown: Option<bool>,
+
+    /// Log a single count of the database rows with unrecognized country codes, instead of a
+    /// warning for each row.
+    #[arg(short = 'q', long = "quiet", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quiet: Option<bool>,
+
+    /// Refuse to start if a database holds no ranges, instead of only warning.
+    #[arg(long = "require-nonempty", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub require_nonempty: Option<bool>,
+
+    /// The number of lookup results to cache, or 0 to disable caching.
+    #[arg(long = "cache-size")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cache_size: Option<usize>,
+
+    /// Allow browsers on this origin (ex. `https://example.com`) to call the API, or any origin if
+    /// `*`. Can be given more than once.
+    ///
+    /// If not given, no CORS headers are sent.
+    #[arg(long = "cors-origin")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cors_origins: Option<Vec<String>>,
+
+    /// The country code (ex. `US`) of the country to return for IP addresses with no associated
+    /// country, instead of not found.
+    #[arg(long = "default-country")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub default_country: Option<Box<str>>,
+
+    /// Serve the `host/{hostname}` route, which resolves a hostname with DNS before searching for
+    /// its IP addresses, making a DNS request for each request to it.
+    #[arg(long = "enable-dns", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub enable_dns: Option<bool>,
+
+    /// Wrap every response body in an envelope, `{"ok":true,"data":...}` or
+    /// `{"ok":false,"error":"..."}`, instead of returning bare results and errors.
+    #[arg(long = "envelope", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub envelope: Option<bool>,
+
+    /// Indent the JSON of response bodies.
+    #[arg(long = "pretty", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub pretty: Option<bool>,
+
+    /// Zero the last 8 bits of IPv4 addresses and the last 80 bits of IPv6 addresses before logging
+    /// them, so that logged lookups do not identify individual hosts.
+    #[arg(long = "anonymize-logs", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub anonymize_logs: Option<bool>,
+
+    /// The HTTP framework to serve the API with.
+    #[arg(long = "framework", value_enum)]
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub framework: Option<Framework>,
+
+    /// Print the configuration in effect, after merging the configuration file and defaults, as
+    /// TOML and exit, instead of serving.
+    #[arg(long = "print-config", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip, default)]
+    pub print_config: Option<bool>,
+
+    /// Parse the databases and exit, instead of serving, without binding any socket.
+    ///
+    /// Exits with an error if a database fails to parse, or with `--require-nonempty`, holds no
+    /// ranges.
+    #[arg(long = "dry-run", num_args = 0..=1, default_missing_value = "true")]
+    #[serde(skip, default)]
+    pub dry_run: Option<bool>,
 }
 
 /// Replaces missing command-line arguments with values pulled from the configuration file or
@@ -83,6 +235,9 @@ pub struct Arguments {
 ///     - `field` is of a type that must be cloned.
 ///     - `default` is a function, not a value.
 ///
+/// 5. Optionally, a list of fields from `Arguments` that have no default value, and are left as
+///    `None` if neither the command-line or configuration file give one. These are cloned.
+///
 /// Trailing commas are optional.
 ///
 /// # Examples
@@ -97,7 +252,8 @@ pub struct Arguments {
 ///     arguments,
 ///     from_config,
 ///     [(ipv4_pair, SocketAddrV4::new(Ipv4Addr::LOCALHOST, 26_000))],
-///     [(ipv4_db_path, || Path::new("/usr/share/tor/geoip").into())]
+///     [(ipv4_db_path, || Path::new("/usr/share/tor/geoip").into())],
+///     [bind]
 /// );
 /// ```
 macro_rules! fill_missing_arguments {
@@ -105,7 +261,8 @@ macro_rules! fill_missing_arguments {
         $arguments:expr,
         $from_config:expr,
         [ $( ( $field:ident, $default:expr $(,)? ) ),+  $(,)? ],
-        [ $( ( $clone_field:ident, $default_fn:expr $(,)? ) ),+  $(,)?] $(,)?
+        [ $( ( $clone_field:ident, $default_fn:expr $(,)? ) ),+  $(,)?]
+        $(, [ $( $optional_field:ident ),+ $(,)? ] )? $(,)?
     ) => {
         $crate::arguments::Arguments {
             $(
@@ -124,6 +281,11 @@ macro_rules! fill_missing_arguments {
                         .unwrap_or_else($default_fn)
                 ),
             )+
+            $($(
+                $optional_field: $arguments
+                    .$optional_field
+                    .or_else(|| $from_config.and_then(|v| v.$optional_field.as_ref().cloned())),
+            )+)?
         }
     };
 }
@@ -137,6 +299,9 @@ pub fn get_config(arguments: Arguments) -> Arguments {
         arguments,
         from_config,
         [
+            (listen_backlog, 1024),
+            (bind_retry, 0),
+            (bind_retry_delay, 100),
             (ipv4_pair, SocketAddrV4::new(Ipv4Addr::LOCALHOST, 26_000)),
             (ipv4_db_len, 200_000),
             (ipv4_db_comment, '#'),
@@ -145,12 +310,33 @@ pub fn get_config(arguments: Arguments) -> Arguments {
                 SocketAddrV6::new(Ipv6Addr::LOCALHOST, 26_000, 0, 0)
             ),
             (ipv6_db_len, 60_000),
-            (ipv6_db_comment, '#')
+            (ipv6_db_comment, '#'),
+            (keep_unknown, false),
+            (quiet, false),
+            (require_nonempty, false),
+            (enable_dns, false),
+            (envelope, false),
+            (pretty, false),
+            (anonymize_logs, false),
+            (cache_size, 0),
+            (framework, Framework::Warp)
         ],
         [
             (config_path, get_default_config_path),
-            (ipv4_db_path, || Path::new("/usr/share/tor/geoip").into()),
-            (ipv6_db_path, || Path::new("/usr/share/tor/geoip6").into())
+            (ipv4_db_path, || discover_database_paths().0),
+            (ipv6_db_path, || discover_database_paths().1),
+            (cors_origins, Vec::new)
+        ],
+        [
+            bind,
+            unix_socket,
+            max_connections,
+            idle_timeout,
+            ipv4_db_glob,
+            ipv6_db_glob,
+            default_country,
+            print_config,
+            dry_run
         ]
     )
 }
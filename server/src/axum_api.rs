@@ -0,0 +1,265 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! An Axum alternative to the Warp routes of `api`, serving the same API.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use axum::{
+    extract::{rejection::PathRejection, Path, Query, State},
+    http::{
+        header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use ip_geo::country_list::all_countries;
+use serde::Serialize;
+use tokio::{net::TcpListener, task::JoinSet};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::{
+    api::shutdown_signal,
+    lookup::{self, AllowQuery, ApiError, ApiVersion, Cache, Coordinates, ReplyFormat},
+    parse::Maps,
+};
+
+/// The state shared by every handler of a version of the API.
+#[derive(Clone)]
+struct ApiState {
+    maps: Maps,
+    cache: Cache,
+    version: ApiVersion,
+    format: ReplyFormat,
+}
+
+/// Construct an Axum `Router` serving the same routes as `api::get_routes`, searching the given
+/// `Maps` and caching results in `cache`.
+///
+/// If `cors_origins` is not empty, browsers on those origins (or any origin, if it holds `*`) may
+/// call the API. Otherwise, no CORS headers are sent.
+///
+/// If `enable_dns` is true, also serves the `host/{hostname}` route.
+///
+/// Every `ApiVersion` is served under its own path prefix. Response bodies are JSON, formatted
+/// according to `ApiVersion::format`.
+///
+/// The router can be served on its own with `serve`, or merged into an existing Axum app.
+///
+/// # Panics
+///
+/// Panics if a CORS origin is not a valid header value.
+pub fn get_router(
+    maps: Maps,
+    cache: Cache,
+    cors_origins: &[String],
+    enable_dns: bool,
+    format: ReplyFormat,
+) -> Router {
+    let mut routes = Router::new()
+        .route("/ipv4/:address", get(search_ipv4))
+        .route("/ipv6/:address", get(search_ipv6))
+        .route("/countries", get(list_countries))
+        .route("/nearest", get(search_nearest_country))
+        .route("/allow/:address", get(search_allowed))
+        .route("/info", get(database_info));
+
+    if enable_dns {
+        routes = routes.route("/host/:host", get(search_host));
+    }
+
+    let router = ApiVersion::ALL
+        .into_iter()
+        .fold(Router::new(), |router, version| {
+            let state = ApiState {
+                maps: maps.clone(),
+                cache: cache.clone(),
+                version,
+                format: version.format(format),
+            };
+
+            router.nest(
+                &format!("/{}", version.path()),
+                routes.clone().with_state(state),
+            )
+        });
+
+    if cors_origins.is_empty() {
+        return router;
+    }
+
+    let allow_origin = if cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(cors_origins.iter().map(|origin| {
+            HeaderValue::from_str(origin).expect("a CORS origin to be a valid header value")
+        }))
+    };
+
+    router.layer(
+        CorsLayer::new()
+            .allow_methods([Method::GET])
+            .allow_headers([IF_NONE_MATCH])
+            .expose_headers([ETAG])
+            .allow_origin(allow_origin),
+    )
+}
+
+/// Serve `router` on each of `listeners` until `shutdown_signal()` resolves, letting in-flight
+/// requests finish.
+pub async fn serve(router: Router, listeners: Vec<TcpListener>) {
+    let mut servers = JoinSet::new();
+
+    for listener in listeners {
+        let router = router.clone();
+        let address = listener.local_addr();
+
+        if let Ok(address) = address {
+            log::info!("Serving on http://{address}/");
+        }
+
+        servers.spawn(async move {
+            if let Err(error) = axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+            {
+                match address {
+                    Ok(address) => log::error!("Failed to serve on {address}: {error}"),
+                    Err(_) => log::error!("Failed to serve: {error}"),
+                }
+            }
+        });
+    }
+
+    while servers.join_next().await.is_some() {}
+}
+
+async fn search_ipv4(
+    State(state): State<ApiState>,
+    ipv4_addr: Result<Path<Ipv4Addr>, PathRejection>,
+    headers: HeaderMap,
+) -> Response {
+    match ipv4_addr {
+        Ok(Path(ipv4_addr)) => search_ip(&state, ipv4_addr.into(), &headers),
+        Err(rejection) => invalid_ip(state.format, rejection),
+    }
+}
+
+async fn search_ipv6(
+    State(state): State<ApiState>,
+    ipv6_addr: Result<Path<Ipv6Addr>, PathRejection>,
+    headers: HeaderMap,
+) -> Response {
+    match ipv6_addr {
+        Ok(Path(ipv6_addr)) => search_ip(&state, ipv6_addr.into(), &headers),
+        Err(rejection) => invalid_ip(state.format, rejection),
+    }
+}
+
+/// Search for an IP address, attaching an `ETag` to a successful response, unless the
+/// `If-None-Match` header already matches it, in which case returns not modified (code 304)
+/// instead.
+///
+/// The search always runs first, so that `If-None-Match: *` only matches an address that has a
+/// country, as a failed search has no representation to match.
+fn search_ip(state: &ApiState, ip_addr: IpAddr, headers: &HeaderMap) -> Response {
+    let lookup = lookup::search_ip(ip_addr, &state.maps, &state.cache);
+    let response = reply(
+        state.format,
+        lookup.map(|lookup| lookup.for_version(state.version)),
+    );
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let etag = lookup::etag(state.maps.version, state.format, ip_addr);
+    let if_none_match = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok());
+
+    let mut response = match if_none_match {
+        Some(tags) if lookup::etag_matches(tags, &etag) => StatusCode::NOT_MODIFIED.into_response(),
+        _ => response,
+    };
+
+    let etag = HeaderValue::from_str(&etag).expect("a hexadecimal ETag to be a valid header");
+    response.headers_mut().insert(ETAG, etag);
+
+    response
+}
+
+async fn list_countries(State(state): State<ApiState>) -> Response {
+    reply(
+        state.format,
+        Ok::<_, ApiError>(all_countries().collect::<Vec<_>>()),
+    )
+}
+
+async fn search_nearest_country(
+    State(state): State<ApiState>,
+    Query(coordinates): Query<Coordinates>,
+) -> Response {
+    reply(state.format, lookup::search_nearest_country(&coordinates))
+}
+
+async fn search_allowed(
+    State(state): State<ApiState>,
+    ip_addr: Result<Path<IpAddr>, PathRejection>,
+    Query(query): Query<AllowQuery>,
+) -> Response {
+    match ip_addr {
+        Ok(Path(ip_addr)) => reply(
+            state.format,
+            lookup::search_allowed(ip_addr, &query, &state.maps),
+        ),
+        Err(rejection) => invalid_ip(state.format, rejection),
+    }
+}
+
+async fn search_host(State(state): State<ApiState>, Path(host): Path<String>) -> Response {
+    reply(
+        state.format,
+        lookup::search_host(&host, &state.maps, &state.cache).await,
+    )
+}
+
+async fn database_info(State(state): State<ApiState>) -> Response {
+    reply(state.format, Ok::<_, ApiError>(&*state.maps.info))
+}
+
+/// Returns a bad request (code 400) for an IP address in the path that could not be parsed, as the
+/// Warp routes do, instead of Axum's plain text rejection.
+fn invalid_ip(format: ReplyFormat, rejection: PathRejection) -> Response {
+    let error = ApiError::invalid_ip(&rejection.body_text());
+
+    reply(format, Err::<(), _>(error))
+}
+
+/// Returns the result of a lookup as a JSON response formatted according to `format`, with the
+/// status code of the error if it failed.
+fn reply(format: ReplyFormat, result: Result<impl Serialize, ApiError>) -> Response {
+    let (status, body) = format.to_json(result);
+
+    (
+        StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        [(CONTENT_TYPE, "application/json")],
+        body,
+    )
+        .into_response()
+}
@@ -15,6 +15,8 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
+use std::{net::SocketAddr, path::Path};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("failed to deserialize TOML data")]
@@ -22,4 +24,43 @@ pub enum Error {
 
     #[error("failed to perform I/O action")]
     Io(#[from] std::io::Error),
+    #[error(transparent)]
+    IpGeo(#[from] ip_geo::Error),
+
+    #[error("the database at {} holds no ranges", .0.display())]
+    EmptyDatabase(Box<Path>),
+
+    #[error("comment character '{0}' is not ASCII")]
+    NonAsciiComment(char),
+
+    #[error("invalid database glob pattern: {0}")]
+    GlobPattern(#[from] glob::PatternError),
+
+    #[error("no database files match {0}")]
+    NoGlobMatches(Box<str>),
+
+    #[error("failed to bind to {target}: {source}")]
+    Bind {
+        target: SocketAddr,
+        source: std::io::Error,
+    },
+
+    #[cfg(unix)]
+    #[error("failed to bind to {}: {source}", .path.display())]
+    BindUnix {
+        path: Box<Path>,
+        source: std::io::Error,
+    },
+
+    #[cfg(not(unix))]
+    #[error("cannot serve on {}, Unix domain sockets are only supported on Unix", .0.display())]
+    UnixSocketUnsupported(Box<Path>),
+
+    #[cfg(feature = "axum")]
+    #[error("cannot serve on {}, Unix domain sockets are only supported with Warp", .0.display())]
+    UnixSocketWithAxum(Box<Path>),
+
+    #[cfg(feature = "axum")]
+    #[error("--max-connections and --idle-timeout are only supported with Warp")]
+    ConnectionLimitsWithAxum,
 }
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! The HTTP API of ip_geo, served by the `ip_geo_server` binary.
+//!
+//! The API can also be embedded into other servers: serve `api::get_routes()` (Warp) or
+//! `axum_api::get_router()` (Axum, with the `axum` feature) alongside other routes, or call
+//! `lookup::resolve()` directly with `Maps` built by `parse::parse_ip_maps()` or `Maps::new()`.
+
+pub mod api;
+pub mod arguments;
+#[cfg(feature = "axum")]
+pub mod axum_api;
+pub mod error;
+pub mod lookup;
+pub mod parse;
@@ -0,0 +1,689 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! The logic behind each API route, independent of the HTTP framework serving it.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    net::IpAddr,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use ip_geo::{
+    anonymize, classify,
+    country_list::{all_countries, eu_members, nearest_country, Country},
+    normalize_addr, AddressClass,
+};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::parse::Maps;
+
+/// The number of trailing bits zeroed from IPv4 and IPv6 addresses before they are logged, if
+/// `ANONYMIZE_LOGS` is set, keeping an IPv4 `/24` and an IPv6 `/48`.
+const ANONYMIZED_BITS: (u8, u8) = (8, 80);
+
+/// Whether to anonymize IP addresses before logging them.
+static ANONYMIZE_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// Set whether IP addresses are anonymized with `ip_geo::anonymize` before they are logged.
+pub fn set_anonymize_logs(anonymize_logs: bool) {
+    ANONYMIZE_LOGS.store(anonymize_logs, Ordering::Relaxed);
+}
+
+/// Return an IP address as it should appear in the logs, anonymized if `set_anonymize_logs` was
+/// set.
+fn loggable(ip_addr: IpAddr) -> IpAddr {
+    match ANONYMIZE_LOGS.load(Ordering::Relaxed) {
+        true => anonymize(ip_addr, ANONYMIZED_BITS.0, ANONYMIZED_BITS.1),
+        false => ip_addr,
+    }
+}
+
+/// A bounded cache of the results of recent lookups, keyed by the queried IP address.
+///
+/// When full, the least recently used result is evicted. Because the maps are never modified while
+/// serving, cached results never need to be invalidated.
+#[derive(Clone)]
+pub struct Cache(Option<Arc<Mutex<CachedResults>>>);
+
+/// The results held by a `Cache`, where `None` is a lookup that found no country.
+type CachedResults = LruCache<IpAddr, Option<Country>>;
+
+impl Cache {
+    /// Create a new `Cache` holding up to `size` results, or a disabled cache if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        Self(NonZeroUsize::new(size).map(|size| Arc::new(Mutex::new(LruCache::new(size)))))
+    }
+
+    /// Return the cached result for an IP address, if there is one.
+    ///
+    /// `Some(None)` is a cached lookup that found no country.
+    fn get(&self, ip_addr: IpAddr) -> Option<Option<Country>> {
+        self.0.as_ref()?.lock().ok()?.get(&ip_addr).cloned()
+    }
+
+    /// Cache the result of a lookup, if it found a country or found that there is none.
+    fn insert(&self, ip_addr: IpAddr, result: &Result<Country, ip_geo::Error>) {
+        let value = match result {
+            Ok(country) => Some(country.clone()),
+            Err(ip_geo::Error::NoValueFound) => None,
+            Err(_) => return,
+        };
+
+        if let Some(Ok(mut cache)) = self.0.as_ref().map(|cache| cache.lock()) {
+            cache.put(ip_addr, value);
+        }
+    }
+}
+
+/// An error response, holding the HTTP status code to respond with.
+///
+/// Serializes to JSON in the format of:
+///
+/// ```json
+/// {"code":"not_found","error":"example error text"}
+/// ```
+///
+/// `code` is stable, so that clients can branch on it, while `error` is meant for humans and may
+/// change. The codes are:
+///
+/// - `invalid_ip`: an IP address in the path could not be parsed (code 400).
+/// - `invalid_coordinates`: the coordinates of `nearest` are out of range (code 400).
+/// - `invalid_country`: a country code of `allow` is empty or not known (code 400).
+/// - `invalid_region`: the region of `allow` is not known (code 400).
+/// - `missing_query`: `allow` was given neither countries nor a region (code 400).
+/// - `not_found`: no country is associated with the IP address (code 404).
+/// - `dns_failure`: the hostname of `host` could not be resolved (code 502).
+/// - `internal`: anything else (code 500).
+#[derive(Serialize, Debug)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: u16,
+    pub code: &'static str,
+    pub error: Box<str>,
+}
+
+impl ApiError {
+    /// Returns a bad request (code 400) for an IP address that could not be parsed.
+    pub fn invalid_ip(error: &str) -> Self {
+        Self::bad_request("invalid_ip", error)
+    }
+
+    /// Returns a bad request (code 400).
+    fn bad_request(code: &'static str, error: &str) -> Self {
+        Self {
+            status: 400,
+            code,
+            error: error.into(),
+        }
+    }
+
+    /// Returns not found (code 404).
+    fn not_found(error: &str) -> Self {
+        Self {
+            status: 404,
+            code: "not_found",
+            error: error.into(),
+        }
+    }
+
+    /// Returns bad gateway (code 502).
+    fn bad_gateway(error: &str) -> Self {
+        Self {
+            status: 502,
+            code: "dns_failure",
+            error: error.into(),
+        }
+    }
+
+    /// Logs `error` and returns an internal server error (code 500).
+    fn internal(error: ip_geo::Error) -> Self {
+        log::error!("Error 500: request resulted in error: '{error}'");
+
+        Self {
+            status: 500,
+            code: "internal",
+            error: error.to_string().into_boxed_str(),
+        }
+    }
+}
+
+impl From<ip_geo::Error> for ApiError {
+    /// Returns not found (code 404) if no country is associated with an IP address, a bad request
+    /// (code 400) for an IP address of the wrong family, or an internal server error (code 500) for
+    /// any other error.
+    fn from(error: ip_geo::Error) -> Self {
+        match error {
+            ip_geo::Error::NoValueFound => Self::not_found("no country associated with IP address"),
+            ip_geo::Error::AddressFamilyMismatch(_) => Self::invalid_ip(&error.to_string()),
+            _ => Self::internal(error),
+        }
+    }
+}
+
+/// A version of the API, served under its own path prefix, so that clients can pin the format of
+/// the responses they expect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// Served under `/v0/`, formatted as configured, with bare countries.
+    V0,
+    /// Served under `/v1/`, always wrapped in an envelope, with lookups of IP addresses extended by
+    /// `ExtendedCountry`.
+    V1,
+}
+
+impl ApiVersion {
+    /// Every version of the API, from oldest to newest.
+    pub const ALL: [Self; 2] = [Self::V0, Self::V1];
+
+    /// The path prefix of the version, ex. `"v1"`.
+    pub fn path(self) -> &'static str {
+        match self {
+            Self::V0 => "v0",
+            Self::V1 => "v1",
+        }
+    }
+
+    /// The format of the version's responses, given the configured `format`.
+    pub fn format(self, format: ReplyFormat) -> ReplyFormat {
+        match self {
+            Self::V0 => format,
+            Self::V1 => ReplyFormat {
+                envelope: true,
+                ..format
+            },
+        }
+    }
+}
+
+/// How the JSON bodies of responses are formatted.
+#[derive(Clone, Copy, Debug, Default, Hash)]
+pub struct ReplyFormat {
+    /// Wrap every body in an envelope, `{"ok":true,"data":...}` on success and
+    /// `{"ok":false,"code":"...","error":"..."}` on failure, so that clients can parse every
+    /// response the same way.
+    pub envelope: bool,
+    /// Indent the JSON, for readability while developing.
+    pub pretty: bool,
+}
+
+/// The body of a response when `ReplyFormat::envelope` is set.
+#[derive(Serialize)]
+struct Envelope<T: Serialize> {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Box<str>>,
+}
+
+impl ReplyFormat {
+    /// Serialize the result of a lookup to JSON, returning the HTTP status code to respond with and
+    /// the body.
+    ///
+    /// Without an envelope, a successful result is serialized bare, and an error as `ApiError`.
+    pub fn to_json(self, result: Result<impl Serialize, ApiError>) -> (u16, String) {
+        let (status, body) = match (result, self.envelope) {
+            (Ok(contents), false) => (200, self.serialize(&contents)),
+            (Err(error), false) => (error.status, self.serialize(&error)),
+            (Ok(contents), true) => (
+                200,
+                self.serialize(&Envelope {
+                    ok: true,
+                    data: Some(contents),
+                    code: None,
+                    error: None,
+                }),
+            ),
+            (Err(error), true) => (
+                error.status,
+                self.serialize(&Envelope::<()> {
+                    ok: false,
+                    data: None,
+                    code: Some(error.code),
+                    error: Some(error.error),
+                }),
+            ),
+        };
+
+        match body {
+            Ok(body) => (status, body),
+            Err(error) => {
+                log::error!("Error 500: failed to serialize response: '{error}'");
+
+                (
+                    500,
+                    r#"{"code":"internal","error":"failed to serialize response"}"#.to_string(),
+                )
+            }
+        }
+    }
+
+    fn serialize(self, value: &impl Serialize) -> serde_json::Result<String> {
+        match self.pretty {
+            true => serde_json::to_string_pretty(value),
+            false => serde_json::to_string(value),
+        }
+    }
+}
+
+/// The query parameters of the `nearest` route.
+#[derive(Deserialize)]
+pub struct Coordinates {
+    lat: f64,
+    lon: f64,
+}
+
+/// The query parameters of the `allow` route.
+///
+/// At least one of `countries` or `region` must be given, and an IP address is allowed if it is in
+/// any of them.
+#[derive(Deserialize)]
+pub struct AllowQuery {
+    /// Comma-separated country codes, ex. `US,CA`, matched case-insensitively.
+    countries: Option<String>,
+    /// A named group of countries, ex. `eu` for the member states of the European Union.
+    region: Option<String>,
+}
+
+/// The response of the `allow` route.
+#[derive(Serialize)]
+pub struct Allowed {
+    allowed: bool,
+}
+
+/// The response of the `ipv4` and `ipv6` routes.
+///
+/// Serializes to the country associated with the IP address, or for an IP address that is not
+/// publicly routable, to its class, ex. `{"class":"private"}`.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Lookup {
+    Country(Country),
+    /// A country with the fields added in `ApiVersion::V1`.
+    Extended(ExtendedCountry),
+    NonPublic {
+        class: &'static str,
+    },
+}
+
+impl Lookup {
+    /// Convert the lookup to the response of a given version of the API.
+    pub fn for_version(self, version: ApiVersion) -> Self {
+        match (self, version) {
+            (Self::Country(country), ApiVersion::V1) => Self::Extended(ExtendedCountry {
+                names: country.names.clone(),
+                eu: country.is_eu(),
+                country,
+            }),
+            (lookup, _) => lookup,
+        }
+    }
+}
+
+/// A country, alongside its names in other languages and whether it is a member state of the
+/// European Union.
+#[derive(Serialize)]
+pub struct ExtendedCountry {
+    #[serde(flatten)]
+    country: Country,
+    /// The names of the country, with the language code as the key, ex. `"fr"` => `"Belgique"`.
+    names: HashMap<Box<str>, Box<str>>,
+    eu: bool,
+}
+
+/// One of the IP addresses a hostname resolved to, in the response of the `host` route.
+#[derive(Serialize)]
+pub struct HostAddress {
+    address: IpAddr,
+    /// `None` if no country is associated with the IP address.
+    country: Option<Country>,
+}
+
+/// For a given IP address of either family, find the country it is associated with in `maps`.
+///
+/// IPv4-mapped and IPv4-compatible IPv6 addresses (ex. `::ffff:1.2.3.4`) are searched in the IPv4
+/// map, as if the IPv4 address were queried directly.
+///
+/// If no country is associated with the IP address, returns `maps.default`, if set.
+///
+/// Holds no HTTP framework types, so it can be called from any server, or none at all.
+pub fn resolve(ip_addr: IpAddr, maps: &Maps) -> Result<Country, ip_geo::Error> {
+    match (ip_geo::search(&maps.v4, &maps.v6, ip_addr), &maps.default) {
+        (Err(ip_geo::Error::NoValueFound), Some(default)) => Ok(default.clone()),
+        (result, _) => result.cloned(),
+    }
+}
+
+/// `resolve` an IP address, consulting `cache` first.
+///
+/// IP addresses that are not publicly routable (ex. `192.168.0.1` or `::1`) are never searched,
+/// returning `Lookup::NonPublic` instead.
+///
+/// Returns not found (code 404) if no country is associated with the IP address, or an internal
+/// server error (code 500) for any other error.
+pub fn search_ip(ip_addr: IpAddr, maps: &Maps, cache: &Cache) -> Result<Lookup, ApiError> {
+    // Normalize first, so that both forms of an IPv4 address share a cache entry
+    let ip_addr = normalize_addr(ip_addr);
+
+    let class = classify(ip_addr);
+    if class != AddressClass::Public {
+        log::debug!("Lookup of {}: {class} address", loggable(ip_addr));
+
+        return Ok(Lookup::NonPublic {
+            class: class.as_str(),
+        });
+    }
+
+    let result = match cache.get(ip_addr) {
+        Some(cached) => cached.ok_or(ip_geo::Error::NoValueFound),
+        None => {
+            let result = resolve(ip_addr, maps);
+            cache.insert(ip_addr, &result);
+
+            result
+        }
+    };
+
+    match &result {
+        Ok(country) => log::debug!("Lookup of {}: {}", loggable(ip_addr), country.code),
+        Err(error) => log::debug!("Lookup of {}: {error}", loggable(ip_addr)),
+    }
+
+    result.map(Lookup::Country).map_err(ApiError::from)
+}
+
+/// Resolve a hostname with DNS, then `search_ip` each of the IP addresses it resolved to.
+///
+/// IP addresses with no associated country, including those that are not publicly routable, are
+/// included with no country. Returns bad gateway (code 502) if the hostname could not be resolved.
+pub async fn search_host(
+    host: &str,
+    maps: &Maps,
+    cache: &Cache,
+) -> Result<Vec<HostAddress>, ApiError> {
+    let mut addresses: Vec<IpAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|error| ApiError::bad_gateway(&format!("failed to resolve hostname: {error}")))?
+        .map(|socket_addr| socket_addr.ip())
+        .collect();
+
+    // The resolver can return the same IP address more than once
+    addresses.sort_unstable();
+    addresses.dedup();
+
+    addresses
+        .into_iter()
+        .map(|address| {
+            let country = match search_ip(address, maps, cache) {
+                Ok(Lookup::Country(country)) => Some(country),
+                Ok(Lookup::Extended(extended)) => Some(extended.country),
+                Ok(Lookup::NonPublic { .. }) => None,
+                Err(error) if error.status == 404 => None,
+                Err(error) => return Err(error),
+            };
+
+            Ok(HostAddress { address, country })
+        })
+        .collect()
+}
+
+/// Returns the ETag of the lookup of an IP address in the databases of a given `Maps::version`, ex.
+/// `"0123456789abcdef"`.
+///
+/// Both forms of an IPv4 address share an ETag, as they resolve to the same country. `format` is
+/// included, as it changes the bytes of the response.
+pub fn etag(version: u64, format: ReplyFormat, ip_addr: IpAddr) -> String {
+    let mut hasher = DefaultHasher::new();
+    (version, format, normalize_addr(ip_addr)).hash(&mut hasher);
+
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Returns true if the value of an `If-None-Match` header, ex. `"abc", W/"def"`, matches `etag`.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Returns the country nearest to the given coordinates.
+///
+/// Returns a bad request (code 400) if the coordinates are out of range.
+pub fn search_nearest_country(
+    Coordinates { lat, lon }: &Coordinates,
+) -> Result<&'static Country, ApiError> {
+    if !(-90.0..=90.0).contains(lat) || !(-180.0..=180.0).contains(lon) {
+        return Err(ApiError::bad_request(
+            "invalid_coordinates",
+            "latitude must be within ±90 and longitude within ±180",
+        ));
+    }
+
+    Ok(nearest_country(*lat, *lon))
+}
+
+/// Returns whether an IP address is associated with one of the countries in `query`.
+///
+/// Ignores `maps.default`, so IP addresses with no associated country are never allowed.
+///
+/// Returns a bad request (code 400) if `query` names neither countries nor a region, or names an
+/// empty or unknown country code or an unknown region.
+///
+/// IPv4-mapped IPv6 addresses are searched in the IPv4 map.
+pub fn search_allowed(
+    ip_addr: IpAddr,
+    query: &AllowQuery,
+    maps: &Maps,
+) -> Result<Allowed, ApiError> {
+    let countries = query
+        .countries
+        .as_deref()
+        .filter(|countries| !countries.trim().is_empty());
+    let countries: Vec<String> = match countries {
+        Some(countries) => countries
+            .split(',')
+            .map(parse_country_code)
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+    let mut codes: HashSet<&str> = countries.iter().map(String::as_str).collect();
+
+    let region = query.region.as_deref().map(str::to_ascii_lowercase);
+
+    match (region.as_deref(), countries.is_empty()) {
+        (Some("eu"), _) => codes.extend(eu_members()),
+        (Some(_), _) => {
+            return Err(ApiError::bad_request(
+                "invalid_region",
+                "unknown region, expected 'eu'",
+            ))
+        }
+        (None, true) => {
+            return Err(ApiError::bad_request(
+                "missing_query",
+                "expected 'countries' or 'region'",
+            ))
+        }
+        (None, false) => {}
+    }
+
+    let allowed = match normalize_addr(ip_addr) {
+        IpAddr::V4(ipv4_addr) => maps.v4.is_in_countries(ipv4_addr, &codes),
+        IpAddr::V6(ipv6_addr) => maps.v6.is_in_countries(ipv6_addr, &codes),
+    };
+
+    if let Ok(allowed) = allowed {
+        log::debug!(
+            "Allow check of {} for {:?}: {allowed}",
+            loggable(ip_addr),
+            codes
+        );
+    }
+
+    allowed
+        .map(|allowed| Allowed { allowed })
+        .map_err(ApiError::from)
+}
+
+/// Uppercase and trim a country code of the `allow` route, ex. `" us"` to `"US"`.
+///
+/// Returns a bad request (code 400) if the code is empty or not known.
+fn parse_country_code(code: &str) -> Result<String, ApiError> {
+    let code = code.trim().to_ascii_uppercase();
+
+    match all_countries().any(|country| *country.code == *code) {
+        true => Ok(code),
+        false => Err(ApiError::bad_request(
+            "invalid_country",
+            &format!("unknown country code '{code}'"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn maps() -> Maps {
+        use crate::parse::DatabaseSource;
+        use ip_geo::IpAddrMap;
+        use std::path::Path;
+
+        let mut ipv4_map = IpAddrMap::new();
+        ipv4_map
+            .insert_range(
+                Ipv4Addr::new(1, 0, 0, 0),
+                Ipv4Addr::new(1, 0, 0, 255),
+                "BE".parse::<Country>().unwrap(),
+            )
+            .unwrap();
+        ipv4_map.cleanup();
+
+        Maps::new(
+            ipv4_map,
+            IpAddrMap::new(),
+            None,
+            DatabaseSource::file(Path::new("geoip").into()),
+            DatabaseSource::file(Path::new("geoip6").into()),
+        )
+    }
+
+    fn allow_query(countries: Option<&str>, region: Option<&str>) -> AllowQuery {
+        AllowQuery {
+            countries: countries.map(String::from),
+            region: region.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = "\"abc\"";
+
+        assert!(etag_matches("\"abc\"", etag));
+        assert!(etag_matches("W/\"abc\"", etag));
+        assert!(etag_matches("*", etag));
+        assert!(etag_matches("\"def\", W/\"abc\"", etag));
+        assert!(etag_matches("\"def\",\"abc\"", etag));
+
+        assert!(!etag_matches("\"def\"", etag));
+        assert!(!etag_matches("W/\"def\", \"ghi\"", etag));
+        assert!(!etag_matches("", etag));
+    }
+
+    #[test]
+    fn test_etag() {
+        let ip_addr = IpAddr::from([1, 0, 0, 1]);
+        let pretty = ReplyFormat {
+            pretty: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            etag(1, ReplyFormat::default(), ip_addr),
+            etag(
+                1,
+                ReplyFormat::default(),
+                IpAddr::from(Ipv4Addr::from([1, 0, 0, 1]).to_ipv6_mapped())
+            )
+        );
+        assert_ne!(
+            etag(1, ReplyFormat::default(), ip_addr),
+            etag(2, ReplyFormat::default(), ip_addr)
+        );
+        assert_ne!(
+            etag(1, ReplyFormat::default(), ip_addr),
+            etag(1, pretty, ip_addr)
+        );
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let cache = Cache::new(1);
+        let (ip_a, ip_b) = (IpAddr::from([1, 0, 0, 1]), IpAddr::from([2, 0, 0, 1]));
+        let belgium = "BE".parse::<Country>().unwrap();
+
+        cache.insert(ip_a, &Ok(belgium.clone()));
+        assert_eq!(cache.get(ip_a), Some(Some(belgium)));
+
+        cache.insert(ip_b, &Err(ip_geo::Error::NoValueFound));
+        assert_eq!(cache.get(ip_b), Some(None));
+        assert_eq!(cache.get(ip_a), None);
+
+        // Other errors are not cached
+        cache.insert(ip_a, &Err(ip_geo::Error::EmptyRangeError));
+        assert_eq!(cache.get(ip_a), None);
+
+        let disabled = Cache::new(0);
+        disabled.insert(ip_a, &Err(ip_geo::Error::NoValueFound));
+        assert_eq!(disabled.get(ip_a), None);
+    }
+
+    #[test]
+    fn test_search_allowed() {
+        let maps = maps();
+        let ip_addr = IpAddr::from([1, 0, 0, 1]);
+        let search =
+            |countries, region| search_allowed(ip_addr, &allow_query(countries, region), &maps);
+
+        assert!(search(Some("us, be"), None).unwrap().allowed);
+        assert!(search(None, Some("EU")).unwrap().allowed);
+        assert!(!search(Some("US"), None).unwrap().allowed);
+
+        let code = |result: Result<Allowed, ApiError>| {
+            let error = result.err().unwrap();
+            assert_eq!(error.status, 400);
+            error.code
+        };
+
+        assert_eq!(code(search(None, None)), "missing_query");
+        assert_eq!(code(search(Some(" "), None)), "missing_query");
+        assert_eq!(code(search(Some("US,XX"), None)), "invalid_country");
+        assert_eq!(code(search(Some("US,,CA"), None)), "invalid_country");
+        assert_eq!(code(search(Some("US"), Some("asia"))), "invalid_region");
+    }
+}
@@ -15,33 +15,144 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use clap::Parser;
-
-#[macro_use]
-mod api;
-
-mod arguments;
-use arguments::Arguments;
+use std::time::Duration;
 
-mod error;
+use clap::Parser;
 
-mod parse;
+use ip_geo_server::{
+    api,
+    arguments::{self, Arguments, Framework},
+    lookup, parse,
+};
+#[cfg(feature = "axum")]
+use ip_geo_server::{api::Listeners, axum_api, error};
 
 #[tokio::main]
 pub async fn main() {
+    // Log `info` and above, unless overridden by `RUST_LOG`
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
     // Parse options
     let arguments = arguments::get_config(Arguments::parse());
 
+    if arguments.print_config.unwrap_or(false) {
+        match toml::to_string(&arguments) {
+            Ok(config) => print!("{config}"),
+            Err(error) => {
+                log::error!("Failed to serialize the configuration: {error}");
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
     // Safety: `arguments::get_config()` implements default values
-    let ipv4_target = arguments.ipv4_pair.unwrap();
-    let ipv6_target = arguments.ipv6_pair.unwrap();
+    #[cfg(feature = "axum")]
+    if let Framework::Axum = arguments.framework.unwrap() {
+        if let Some(path) = &arguments.unix_socket {
+            log::error!("{}", error::Error::UnixSocketWithAxum(path.clone()));
+            std::process::exit(1);
+        }
+
+        if arguments.max_connections.is_some() || arguments.idle_timeout.is_some() {
+            log::error!("{}", error::Error::ConnectionLimitsWithAxum);
+            std::process::exit(1);
+        }
+    }
+
+    if arguments.dry_run.unwrap_or(false) {
+        if let Err(error) = parse::parse_ip_maps(&arguments) {
+            log::error!("Failed to parse databases: {error}");
+            std::process::exit(1);
+        }
+
+        log::info!("Parsed databases, exiting without serving");
+        return;
+    }
+
+    // Bind before parsing the databases, so that an address in use fails startup quickly, unless
+    // asked to retry
+    let listeners = match arguments.unix_socket.clone() {
+        Some(path) => api::bind_unix(path),
+        None => {
+            let targets = match arguments.bind {
+                Some(target) => vec![target],
+                // Safety: `arguments::get_config()` implements default values
+                None => vec![
+                    arguments.ipv4_pair.unwrap().into(),
+                    arguments.ipv6_pair.unwrap().into(),
+                ],
+            };
+
+            // Safety: `arguments::get_config()` implements default values
+            let retry = api::BindRetry {
+                attempts: arguments.bind_retry.unwrap(),
+                delay: Duration::from_millis(arguments.bind_retry_delay.unwrap()),
+            };
+
+            // Safety: `arguments::get_config()` implements default values
+            api::bind(targets, arguments.listen_backlog.unwrap(), retry).await
+        }
+    };
+    let listeners = match listeners {
+        Ok(listeners) => listeners,
+        Err(error) => {
+            log::error!("{error}");
+            std::process::exit(1);
+        }
+    };
+
+    // Safety: `arguments::get_config()` implements default values
+    lookup::set_anonymize_logs(arguments.anonymize_logs.unwrap());
+
+    let (generated_at, count) = ip_geo::country_list::provenance();
+    log::info!("Country list: {count} entries, generated {generated_at}");
 
     // Parse databases
-    let maps = parse::parse_ip_maps(&arguments);
+    let maps = match parse::parse_ip_maps(&arguments) {
+        Ok(maps) => maps,
+        Err(error) => {
+            log::error!("Failed to parse databases: {error}");
+            std::process::exit(1);
+        }
+    };
 
     // Construct routes
-    let routes = api::get_routes(maps);
+    // Safety: `arguments::get_config()` implements default values
+    let cache = lookup::Cache::new(arguments.cache_size.unwrap());
+    let cors_origins = arguments.cors_origins.unwrap();
+    let enable_dns = arguments.enable_dns.unwrap();
+    let format = lookup::ReplyFormat {
+        envelope: arguments.envelope.unwrap(),
+        pretty: arguments.pretty.unwrap(),
+    };
+
+    // Serve routes until a shutdown signal is received
+    // Safety: `arguments::get_config()` implements default values
+    match arguments.framework.unwrap() {
+        Framework::Warp => {
+            let routes = api::get_routes(maps, cache, &cors_origins, enable_dns, format);
+            let limits = api::ConnectionLimits {
+                max_connections: arguments.max_connections,
+                idle_timeout: arguments.idle_timeout.map(Duration::from_secs),
+            };
+
+            api::serve(routes, listeners, limits).await;
+        }
+        #[cfg(feature = "axum")]
+        Framework::Axum => {
+            let router = axum_api::get_router(maps, cache, &cors_origins, enable_dns, format);
+
+            match listeners {
+                Listeners::Tcp(listeners) => axum_api::serve(router, listeners).await,
+                #[cfg(unix)]
+                Listeners::Unix(..) => {
+                    unreachable!("a Unix domain socket to be rejected with Axum")
+                }
+            }
+        }
+    }
 
-    // Serve routes
-    serve!(routes, ipv4_target, ipv6_target);
+    log::info!("Shut down gracefully");
 }
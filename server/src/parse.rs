@@ -16,61 +16,372 @@
 // not, see <https://www.gnu.org/licenses/>.
 
 use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
     net::{Ipv4Addr, Ipv6Addr},
+    path::Path,
     sync::Arc,
+    time::UNIX_EPOCH,
 };
 
-use ip_geo::{country_list::Country, IpAddrMap};
+use ip_geo::{
+    country_list::Country,
+    parse::{ParseOptions, ParseReport},
+    IpAddrMap,
+};
+use serde::Serialize;
 
-use crate::arguments::Arguments;
+use crate::{arguments::Arguments, error::Error};
 
 /// For a given set of `Arguments`, parse the specified IPv4 and IPv6 databases into `IpAddrMap`s
 /// and return them in a struct holding them as `Arc`s.
-pub fn parse_ip_maps(arguments: &Arguments) -> Maps {
-    Maps::new(parse_ipv4(arguments), parse_ipv6(arguments))
+///
+/// Returns `ip_geo::Error::InvalidCode` before parsing anything if the default country is not a
+/// recognized country.
+pub fn parse_ip_maps(arguments: &Arguments) -> Result<Maps, Error> {
+    let default = arguments
+        .default_country
+        .as_deref()
+        .map(str::parse::<Country>)
+        .transpose()?;
+
+    let (ipv4_map, ipv4_database) = parse_ipv4(arguments)?;
+    let (ipv6_map, ipv6_database) = parse_ipv6(arguments)?;
+
+    Ok(Maps::new(
+        ipv4_map,
+        ipv6_map,
+        default,
+        ipv4_database,
+        ipv6_database,
+    ))
 }
 
 /// A simple struct for passing around `IpAddrMaps`.
+#[derive(Clone)]
 pub struct Maps {
     pub v4: Arc<IpAddrMap<Ipv4Addr, Country>>,
     pub v6: Arc<IpAddrMap<Ipv6Addr, Country>>,
+    /// The country to return for IP addresses with no associated country, if any.
+    pub default: Option<Country>,
+    /// A hash of the contents of both maps and the default country, which changes whenever either
+    /// does.
+    pub version: u64,
+    /// Metadata about the loaded databases.
+    pub info: Arc<Info>,
 }
 
 impl Maps {
-    /// Create a new `Maps` from IPv4 and IPv6 `IpAddrMap`s.
+    /// Create a new `Maps` from IPv4 and IPv6 `IpAddrMap`s, an optional default country, and the
+    /// databases the maps were parsed from.
     pub fn new(
         ipv4_map: IpAddrMap<Ipv4Addr, Country>,
         ipv6_map: IpAddrMap<Ipv6Addr, Country>,
+        default: Option<Country>,
+        ipv4_database: DatabaseSource,
+        ipv6_database: DatabaseSource,
     ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for entry in &ipv4_map {
+            (entry.start(), entry.end(), &entry.value().code).hash(&mut hasher);
+        }
+        for entry in &ipv6_map {
+            (entry.start(), entry.end(), &entry.value().code).hash(&mut hasher);
+        }
+        default
+            .as_ref()
+            .map(|country| &country.code)
+            .hash(&mut hasher);
+
+        let version = hasher.finish();
+        let info = Info {
+            ipv4: DatabaseInfo::new(ipv4_database, ipv4_map.len()),
+            ipv6: DatabaseInfo::new(ipv6_database, ipv6_map.len()),
+            hash: format!("{version:016x}").into_boxed_str(),
+        };
+
         Self {
             v4: Arc::new(ipv4_map),
             v6: Arc::new(ipv6_map),
+            default,
+            version,
+            info: Arc::new(info),
+        }
+    }
+}
+
+/// Metadata about the loaded databases, served by the `info` route.
+///
+/// Serializes to JSON in the format of (without whitespace):
+///
+/// ```json
+/// {
+///   "ipv4": {"path": "/usr/share/tor/geoip", "modified": 1719792000, "entries": 198231},
+///   "ipv6": {"path": "/usr/share/tor/geoip6", "modified": 1719792000, "entries": 58812},
+///   "hash": "0123456789abcdef"
+/// }
+/// ```
+///
+/// A database loaded from a glob pattern also lists each matched file, with `path` as the pattern
+/// and `modified` as the newest of them:
+///
+/// ```json
+/// {
+///   "path": "geoip.d/*.csv",
+///   "modified": 1719792000,
+///   "files": [
+///     {"path": "geoip.d/a.csv", "modified": 1719705600},
+///     {"path": "geoip.d/b.csv", "modified": 1719792000}
+///   ],
+///   "entries": 198231
+/// }
+/// ```
+#[derive(Serialize, Debug)]
+pub struct Info {
+    pub ipv4: DatabaseInfo,
+    pub ipv6: DatabaseInfo,
+    /// `Maps::version` as hexadecimal.
+    pub hash: Box<str>,
+}
+
+/// Metadata about a loaded database.
+#[derive(Serialize, Debug)]
+pub struct DatabaseInfo {
+    /// The path of the database, or its glob pattern.
+    pub path: Box<Path>,
+    /// When the file (or the newest of the matched files) was last modified, as seconds since the
+    /// Unix epoch, if known.
+    pub modified: Option<u64>,
+    /// The files matched by the glob pattern, if one was given.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<DatabaseFile>,
+    /// The number of entries in the parsed map.
+    pub entries: usize,
+}
+
+impl DatabaseInfo {
+    fn new(
+        DatabaseSource {
+            path,
+            modified,
+            files,
+        }: DatabaseSource,
+        entries: usize,
+    ) -> Self {
+        Self {
+            path,
+            modified,
+            files,
+            entries,
         }
     }
 }
 
-/// For a given set of arguments, parse and return the IPv4 database into a clean `IpAddrMap`.
-fn parse_ipv4(arguments: &Arguments) -> IpAddrMap<Ipv4Addr, Country> {
+/// A database, as it was before it was parsed, from either one file or the files matching a glob
+/// pattern.
+pub struct DatabaseSource {
+    path: Box<Path>,
+    modified: Option<u64>,
+    files: Vec<DatabaseFile>,
+}
+
+impl DatabaseSource {
+    /// Read the modification time of the file at `path`, if possible.
+    pub fn file(path: Box<Path>) -> Self {
+        let DatabaseFile { path, modified } = DatabaseFile::new(path);
+
+        Self {
+            path,
+            modified,
+            files: Vec::new(),
+        }
+    }
+
+    /// Record the files matching the glob `pattern`, taking the newest of their modification
+    /// times.
+    pub fn glob(pattern: Box<Path>, files: Vec<DatabaseFile>) -> Self {
+        Self {
+            path: pattern,
+            modified: files.iter().filter_map(|file| file.modified).max(),
+            files,
+        }
+    }
+}
+
+/// A database file, as it was before it was parsed.
+#[derive(Serialize, Debug)]
+pub struct DatabaseFile {
+    pub path: Box<Path>,
+    /// When the file was last modified, as seconds since the Unix epoch, if known.
+    pub modified: Option<u64>,
+}
+
+impl DatabaseFile {
+    /// Read the modification time of the file at `path`, if possible.
+    pub fn new(path: Box<Path>) -> Self {
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        Self { path, modified }
+    }
+}
+
+/// Return the path of the IPv4 database, or its glob pattern, if given.
+fn ipv4_source(arguments: &Arguments) -> Box<Path> {
+    match &arguments.ipv4_db_glob {
+        Some(pattern) => Path::new(pattern.as_ref()).into(),
+        // Safety: `arguments::get_config()` implements default values
+        None => arguments.ipv4_db_path.clone().unwrap(),
+    }
+}
+
+/// Return the path of the IPv6 database, or its glob pattern, if given.
+fn ipv6_source(arguments: &Arguments) -> Box<Path> {
+    match &arguments.ipv6_db_glob {
+        Some(pattern) => Path::new(pattern.as_ref()).into(),
+        // Safety: `arguments::get_config()` implements default values
+        None => arguments.ipv6_db_path.clone().unwrap(),
+    }
+}
+
+/// For a given set of arguments, parse and return the IPv4 database, and the file or files it was
+/// parsed from, into a clean `IpAddrMap`.
+///
+/// If a glob pattern is given, every matching file is appended into one map.
+fn parse_ipv4(
+    arguments: &Arguments,
+) -> Result<(IpAddrMap<Ipv4Addr, Country>, DatabaseSource), Error> {
+    let path = ipv4_source(arguments);
     // Safety: `arguments::get_config()` implements default values
-    let path = arguments.ipv4_db_path.clone().unwrap();
     let file_length = arguments.ipv4_db_len.unwrap();
-    let comment = arguments.ipv4_db_comment;
+    let options = ParseOptions {
+        comment: arguments.ipv4_db_comment.map(char_to_byte).transpose()?,
+        keep_unknown: arguments.keep_unknown.unwrap(),
+        quiet: arguments.quiet.unwrap(),
+        ..Default::default()
+    };
 
-    let mut map = ip_geo::ipv4::parse_ipv4_file(path, file_length, comment);
-    map.cleanup();
+    let (map, database) = match &arguments.ipv4_db_glob {
+        Some(pattern) => {
+            let (map, files) = parse_glob(
+                pattern,
+                options,
+                "IPv4",
+                IpAddrMap::<Ipv4Addr, Country>::append_from_file,
+            )?;
 
-    map
+            (map, DatabaseSource::glob(path.clone(), files))
+        }
+        None => {
+            let database = DatabaseSource::file(path.clone());
+            let (map, report) = ip_geo::ipv4::parse_ipv4_file(path.clone(), file_length, options)?;
+            log::info!("IPv4 database: {report}");
+
+            (map, database)
+        }
+    };
+
+    if map.is_empty() && arguments.require_nonempty.unwrap() {
+        return Err(Error::EmptyDatabase(path));
+    }
+
+    Ok((map, database))
 }
 
-/// For a given set of arguments, parse and return the IPv6 database into an `IpAddrMap`.
-fn parse_ipv6(arguments: &Arguments) -> IpAddrMap<Ipv6Addr, Country> {
+/// For a given set of arguments, parse and return the IPv6 database, and the file or files it was
+/// parsed from, into an `IpAddrMap`.
+///
+/// If a glob pattern is given, every matching file is appended into one map.
+fn parse_ipv6(
+    arguments: &Arguments,
+) -> Result<(IpAddrMap<Ipv6Addr, Country>, DatabaseSource), Error> {
+    let path = ipv6_source(arguments);
     // Safety: `arguments::get_config()` implements default values
-    let path = arguments.ipv6_db_path.clone().unwrap();
     let file_length = arguments.ipv6_db_len.unwrap();
-    let comment = arguments.ipv6_db_comment;
+    let options = ParseOptions {
+        comment: arguments.ipv6_db_comment.map(char_to_byte).transpose()?,
+        keep_unknown: arguments.keep_unknown.unwrap(),
+        quiet: arguments.quiet.unwrap(),
+        ..Default::default()
+    };
+
+    let (map, database) = match &arguments.ipv6_db_glob {
+        Some(pattern) => {
+            let (map, files) = parse_glob(
+                pattern,
+                options,
+                "IPv6",
+                IpAddrMap::<Ipv6Addr, Country>::append_from_file,
+            )?;
+
+            (map, DatabaseSource::glob(path.clone(), files))
+        }
+        None => {
+            let database = DatabaseSource::file(path.clone());
+            let (map, report) = ip_geo::ipv6::parse_ipv6_file(path.clone(), file_length, options)?;
+            log::info!("IPv6 database: {report}");
+
+            (map, database)
+        }
+    };
+
+    if map.is_empty() && arguments.require_nonempty.unwrap() {
+        return Err(Error::EmptyDatabase(path));
+    }
+
+    Ok((map, database))
+}
+
+/// Parse every file matching the glob `pattern` as a shard of one database, appending them in
+/// alphabetical order with `append`, then clean the combined map once.
+///
+/// Returns the combined map and each matched file, as it was before it was parsed.
+///
+/// Returns `Error::NoGlobMatches` if no files match. `name` names the database in the logged
+/// report of each file, ex. `"IPv4"`.
+fn parse_glob<A: Ord + Copy>(
+    pattern: &str,
+    options: ParseOptions,
+    name: &str,
+    append: impl Fn(
+        &mut IpAddrMap<A, Country>,
+        &Path,
+        ParseOptions,
+    ) -> Result<ParseReport, ip_geo::Error>,
+) -> Result<(IpAddrMap<A, Country>, Vec<DatabaseFile>), Error> {
+    let mut map = IpAddrMap::new();
+    let mut files = Vec::new();
+
+    for path in glob::glob(pattern)? {
+        let path = path.map_err(io::Error::from)?;
+        let file = DatabaseFile::new(path.into());
+
+        let report = append(&mut map, &file.path, options)?;
+        log::info!("{name} database {}: {report}", file.path.display());
+
+        files.push(file);
+    }
+
+    if files.is_empty() {
+        return Err(Error::NoGlobMatches(pattern.into()));
+    }
 
-    let mut map = ip_geo::ipv6::parse_ipv6_file(path, file_length, comment);
     map.cleanup();
 
-    map
+    Ok((map, files))
+}
+
+/// Converts an ASCII char to a byte.
+///
+/// Returns `Error::NonAsciiComment` if `char` is not ASCII, as it could never match the first
+/// byte of a line.
+fn char_to_byte(char: char) -> Result<u8, Error> {
+    match char.is_ascii() {
+        true => Ok(char as u8),
+        false => Err(Error::NonAsciiComment(char)),
+    }
 }
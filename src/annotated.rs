@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! Values annotated with metadata about the range they were found in, for databases that record
+//! where and when each range was registered, like the delegation files of the Regional Internet
+//! Registries.
+
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
+};
+
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    country_list::{get_countries, Country},
+    parse::{find_country, CountryMaps, ParseOptions, ParseReport, RowErrors},
+    Error, IpAddrMap,
+};
+
+/// A value, alongside where and when its range was registered.
+///
+/// Serializes as the fields of `data`, alongside `source` and `registered`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::{annotated::Annotated, country_list::Country};
+///
+/// let annotated = Annotated {
+///     data: "BE".parse::<Country>().unwrap(),
+///     source: "ripencc".into(),
+///     registered: Some("20100712".into()),
+/// };
+///
+/// assert_eq!(annotated.data.code, "BE".into());
+/// assert_eq!(Annotated::new(annotated.data.clone(), "ripencc").registered, None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Annotated<C> {
+    /// The value of the range, ex. a `Country`.
+    #[serde(flatten)]
+    pub data: C,
+    /// Where the range came from, ex. the registry that delegated it, like `"ripencc"`.
+    pub source: Box<str>,
+    /// When the range was registered, as given by the source, ex. `"20100712"`, if known.
+    pub registered: Option<Box<str>>,
+}
+
+impl<C> Annotated<C> {
+    /// Annotate `data` with its `source`, without a registration date.
+    pub fn new(data: C, source: impl Into<Box<str>>) -> Self {
+        Self {
+            data,
+            source: source.into(),
+            registered: None,
+        }
+    }
+}
+
+/// Parse a Regional Internet Registry delegation file (ex. `delegated-ripencc-latest`) into
+/// `IpAddrMap`s holding IPv4 and IPv6 addresses, with each country annotated with the registry
+/// that delegated its range and the date it was registered.
+///
+/// Like `parse::parse_rir_delegated()`, but keeps the annotations.
+///
+/// Delegation files hold one record per line, in the format
+/// `registry|cc|type|start|value|date|status`, optionally followed by an opaque ID in the extended
+/// format. For `ipv4` records, `value` is the number of addresses in the range, and for `ipv6`
+/// records, it is the prefix length. The version line, summary lines, comments, and records of
+/// other types (ex. `asn`) are ignored.
+///
+/// Only `allocated` and `assigned` ranges are kept. Dates that are empty or all zeroes are left
+/// unknown.
+///
+/// Records that can't be parsed are skipped and logged as a warning. If more than half of the
+/// records can't be parsed, returns `Error::WrongDatabaseFormat`.
+///
+/// # Panics
+///
+/// Panics if the file at `path` can't be opened.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{io::Write, net::{Ipv4Addr, Ipv6Addr}, str::FromStr};
+/// use ip_geo::annotated::parse_rir_delegated_annotated;
+///
+/// let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+/// write!(
+///     temp_file,
+///     "2|ripencc|20240101|3|19830705|20240101|+0100\n\
+///      ripencc|*|ipv4|*|2|summary\n\
+///      ripencc|BE|ipv4|2.0.0.0|1024|20100712|allocated\n\
+///      ripencc|NL|ipv6|2001:610::|32|00000000|assigned\n\
+///      ripencc||ipv4|3.0.0.0|256||available\n"
+/// )
+/// .unwrap();
+///
+/// let (mut ipv4_map, mut ipv6_map) = parse_rir_delegated_annotated(temp_file.path()).unwrap();
+///
+/// let belgium = ipv4_map.search(Ipv4Addr::new(2, 0, 3, 255)).unwrap();
+/// assert_eq!(belgium.data.code, "BE".into());
+/// assert_eq!(belgium.source.as_ref(), "ripencc");
+/// assert_eq!(belgium.registered.as_deref(), Some("20100712"));
+/// assert!(ipv4_map.search(Ipv4Addr::new(2, 0, 4, 0)).is_err());
+///
+/// let netherlands = Ipv6Addr::from_str("2001:610:ffff::1").unwrap();
+/// let netherlands = ipv6_map.search(netherlands).unwrap();
+/// assert_eq!(netherlands.data.code, "NL".into());
+/// assert_eq!(netherlands.registered, None);
+/// ```
+pub fn parse_rir_delegated_annotated(
+    path: &Path,
+) -> Result<CountryMaps<Annotated<Country>>, Error> {
+    #[derive(Deserialize, Debug)]
+    struct Schema {
+        registry: Box<str>,
+        country_code: Box<str>,
+        kind: Box<str>,
+        start: IpAddr,
+        value: u32,
+        date: Box<str>,
+        status: Box<str>,
+    }
+
+    /// The number of fields of a record, not counting the opaque ID of the extended format.
+    const FIELDS: usize = 7;
+
+    /// Returns true if `record` holds a range of IP addresses, rather than a version line, a
+    /// summary line, or a record of another type.
+    fn is_ip_range(record: &StringRecord) -> bool {
+        matches!(record.get(2), Some("ipv4" | "ipv6")) && record.get(5) != Some("summary")
+    }
+
+    let file = fs::File::open(path).unwrap_or_else(|_| {
+        panic!(
+            "Could not open RIR delegation file at {}",
+            path.to_string_lossy()
+        )
+    });
+    let options = ParseOptions {
+        comment: Some(b'#'),
+        delimiter: b'|',
+        quoting: false,
+        trim_whitespace: true,
+        ..Default::default()
+    };
+    let mut builder = options.reader_builder();
+    let mut reader = builder.flexible(true).from_reader(file);
+
+    let mut ipv4_map = IpAddrMap::new();
+    let mut ipv6_map = IpAddrMap::new();
+    let countries = get_countries();
+    let mut row_errors = RowErrors::default();
+    let mut report = ParseReport::default();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) if options.is_skipped(&record) || !is_ip_range(&record) => continue,
+            record => record,
+        };
+
+        report.total_rows += 1;
+
+        let data = record.and_then(|record| {
+            let record: StringRecord = record.iter().take(FIELDS).collect();
+            record.deserialize::<Schema>(None)
+        });
+        let Some(data) = row_errors.record(data) else {
+            report.skipped_malformed += 1;
+            continue;
+        };
+
+        // Only add ranges that have been delegated to someone
+        if !matches!(data.status.as_ref(), "allocated" | "assigned") {
+            report.skipped_placeholder += 1;
+            continue;
+        }
+
+        let Some(country) = find_country(&countries, &data.country_code) else {
+            options.warn_unknown_code(&data.country_code);
+            report.skipped_unknown_code += 1;
+            continue;
+        };
+
+        let registered = Some(data.date).filter(|date| !date.bytes().all(|byte| byte == b'0'));
+        let annotated = Annotated {
+            data: country,
+            source: data.registry,
+            registered,
+        };
+
+        let inserted = match (data.kind.as_ref(), data.start) {
+            ("ipv4", IpAddr::V4(start)) if data.value != 0 => {
+                let end = u64::from(start.to_bits()) + u64::from(data.value) - 1;
+
+                u32::try_from(end)
+                    .ok()
+                    .map(|end| ipv4_map.insert_range(start, Ipv4Addr::from_bits(end), annotated))
+            }
+            ("ipv6", IpAddr::V6(start)) if data.value <= Ipv6Addr::BITS => {
+                let host_mask = u128::MAX.checked_shr(data.value).unwrap_or(0);
+                let end = Ipv6Addr::from_bits(start.to_bits() | host_mask);
+
+                Some(ipv6_map.insert_range(start, end, annotated))
+            }
+            _ => None,
+        };
+
+        match inserted {
+            Some(result) => result?,
+            None => {
+                log::warn!(
+                    "Skipped invalid {} range at {} of size {}",
+                    data.kind,
+                    data.start,
+                    data.value
+                );
+                report.skipped_malformed += 1;
+                continue;
+            }
+        }
+
+        report.inserted += 1;
+    }
+
+    row_errors.check()?;
+    options.warn_unknown_codes(&report);
+    log::info!("RIR delegation file: {report}");
+
+    ipv4_map.cleanup();
+    ipv6_map.cleanup();
+
+    Ok((ipv4_map, ipv6_map))
+}
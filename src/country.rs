@@ -15,15 +15,257 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    str::FromStr,
+    sync::Arc,
+};
 
 use serde::Serialize;
 
-use crate::country_list::Country;
+use crate::{
+    country_list::{eu_members, get_countries, Country},
+    Error, IpAddrMap,
+};
 
+impl Country {
+    /// Format as the country code, the name, and the coordinates.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::get_countries;
+    ///
+    /// let countries = get_countries();
+    /// let belgium = countries.get("BE").unwrap();
+    ///
+    /// assert_eq!(
+    ///     belgium.format_verbose(),
+    ///     "BE Belgium (4.668055555, 50.641111111)"
+    /// );
+    /// ```
+    pub fn format_verbose(&self) -> String {
+        let (longitude, latitude) = self.coordinates;
+
+        format!("{self} ({longitude}, {latitude})")
+    }
+
+    /// Return the name of the country in a given language, ex. `"fr"`.
+    ///
+    /// `name` is in English, so English falls back to it if `names` has no English entry. Returns
+    /// `None` for other languages missing from `names`, in which case callers should use `name`.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::get_countries;
+    ///
+    /// let countries = get_countries();
+    /// let belgium = countries.get("BE").unwrap();
+    ///
+    /// assert_eq!(belgium.name_in("en"), Some("Belgium"));
+    /// assert_eq!(belgium.name_in("xx"), None);
+    /// ```
+    pub fn name_in(&self, language: &str) -> Option<&str> {
+        if let Some(name) = self.names.get(language) {
+            return Some(name);
+        }
+
+        language.eq_ignore_ascii_case("en").then_some(&self.name)
+    }
+
+    /// Return the flag emoji of the country, formed from the Unicode regional indicator symbols of
+    /// its country code.
+    ///
+    /// Returns an empty string if the code is not two ASCII letters, ex. for the `"??"` sentinel.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::get_countries;
+    ///
+    /// let countries = get_countries();
+    ///
+    /// assert_eq!(countries.get("BE").unwrap().flag_emoji(), "🇧🇪");
+    /// assert_eq!(countries.get("??").unwrap().flag_emoji(), "");
+    /// ```
+    pub fn flag_emoji(&self) -> String {
+        /// The regional indicator symbol for the letter A.
+        const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+
+        if self.code.len() != 2 || !self.code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return String::new();
+        }
+
+        self.code
+            .chars()
+            .filter_map(|c| {
+                char::from_u32(REGIONAL_INDICATOR_A + (c.to_ascii_uppercase() as u32 - 'A' as u32))
+            })
+            .collect()
+    }
+
+    /// Return whether the country is a member state of the European Union, per
+    /// `country_list::EU_MEMBERS`.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::get_countries;
+    ///
+    /// let countries = get_countries();
+    ///
+    /// assert!(countries.get("BE").unwrap().is_eu());
+    /// assert!(!countries.get("CH").unwrap().is_eu());
+    /// ```
+    pub fn is_eu(&self) -> bool {
+        eu_members().contains(self.code.as_ref())
+    }
+
+    /// Return whether every field of two countries is equal, unlike `==`, which compares only the
+    /// country codes.
+    ///
+    /// Use this to tell apart two versions of the same country, ex. from two country lists with
+    /// different names or coordinates.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::get_countries;
+    ///
+    /// let countries = get_countries();
+    /// let belgium = countries.get("BE").unwrap().clone();
+    ///
+    /// let mut renamed = belgium.clone();
+    /// renamed.name = "Kingdom of Belgium".into();
+    ///
+    /// assert!(belgium == renamed);
+    /// assert!(!belgium.eq_full(&renamed));
+    /// assert!(belgium.eq_full(&belgium.clone()));
+    /// ```
+    pub fn eq_full(&self, other: &Self) -> bool {
+        self.code == other.code
+            && self.name == other.name
+            && self.coordinates == other.coordinates
+            && self.names == other.names
+            && self.timezones == other.timezones
+            && self.calling_code == other.calling_code
+    }
+}
+
+impl<A: Ord + Copy> IpAddrMap<A, Country> {
+    /// For a given IP address, return whether the country it is associated with has one of the
+    /// given country codes.
+    ///
+    /// Returns `Ok(false)` if no country is associated with the IP address.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::{collections::HashSet, net::Ipv4Addr};
+    /// use ip_geo::{country_list::Country, IpAddrMap};
+    ///
+    /// let belgium: Country = "BE".parse().unwrap();
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), belgium)
+    ///     .unwrap();
+    ///
+    /// let allowed = HashSet::from(["BE", "NL"]);
+    ///
+    /// assert!(map.is_in_countries(Ipv4Addr::new(2, 2, 2, 2), &allowed).unwrap());
+    /// assert!(!map.is_in_countries(Ipv4Addr::new(5, 5, 5, 5), &allowed).unwrap());
+    /// ```
+    pub fn is_in_countries(&self, address: A, codes: &HashSet<&str>) -> Result<bool, Error> {
+        match self.try_search(address) {
+            Ok(country) => Ok(codes.contains(country.code.as_ref())),
+            Err(Error::NoValueFound) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl Display for Country {
+    /// Format as the country code and the name, separated by a space.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::get_countries;
+    ///
+    /// let countries = get_countries();
+    ///
+    /// assert_eq!(countries.get("BE").unwrap().to_string(), "BE Belgium");
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code, self.name)
+    }
+}
+
+impl FromStr for Country {
+    type Err = Error;
+
+    /// Look up a country by its two letter country code.
+    ///
+    /// Returns `Error::InvalidCode` if the code is malformed or is not a recognized country.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use ip_geo::country_list::Country;
+    ///
+    /// let belgium: Country = "BE".parse().unwrap();
+    /// assert_eq!(belgium.name, "Belgium".into());
+    ///
+    /// assert!("XX".parse::<Country>().is_err());
+    /// assert!("Belgium".parse::<Country>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 2 {
+            return Err(Error::InvalidCode(s.into()));
+        }
+
+        get_countries()
+            .get(s)
+            .cloned()
+            .ok_or_else(|| Error::InvalidCode(s.into()))
+    }
+}
+
+/// Countries are equal if their country codes are equal, regardless of their other fields. Hashing
+/// is likewise by country code alone.
+///
+/// `IpAddrMap` relies on this when cleaning up: entries with the same range and the same country
+/// code are duplicates, and only one is kept even if their names or coordinates differ. Use
+/// `Country::eq_full()` to compare every field.
 impl PartialEq for Country {
     fn eq(&self, other: &Self) -> bool {
-        self.code == other.code // && self.name == other.name
+        self.code == other.code
+    }
+}
+
+impl Eq for Country {}
+
+/// Countries are hashed by their country codes alone, consistent with `PartialEq`, so that they
+/// can be used as the keys of a `HashMap` or the values of a `HashSet`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::collections::HashSet;
+/// use ip_geo::country_list::Country;
+///
+/// let belgium: Country = "BE".parse().unwrap();
+/// let mut renamed = belgium.clone();
+/// renamed.name = "Belgique".into();
+///
+/// let countries = HashSet::from([belgium, renamed, "CA".parse().unwrap()]);
+/// assert_eq!(countries.len(), 2);
+/// ```
+impl Hash for Country {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.code.hash(state);
     }
 }
 
@@ -31,15 +273,28 @@ impl PartialEq for Country {
 struct SerializableCountry {
     code: Box<str>,
     name: Box<str>,
+    flag: Box<str>,
     coordinates: (f64, f64),
+    timezones: Box<[Box<str>]>,
+    calling_code: Option<Box<str>>,
 }
 
 impl SerializableCountry {
-    fn new(code: Box<str>, name: Box<str>, coordinates: (f64, f64)) -> Self {
+    fn new(
+        code: Box<str>,
+        name: Box<str>,
+        flag: Box<str>,
+        coordinates: (f64, f64),
+        timezones: Box<[Box<str>]>,
+        calling_code: Option<Box<str>>,
+    ) -> Self {
         Self {
             code,
             name,
+            flag,
             coordinates,
+            timezones,
+            calling_code,
         }
     }
 }
@@ -47,8 +302,16 @@ impl SerializableCountry {
 impl From<Country> for SerializableCountry {
     fn from(value: Country) -> Self {
         let to_box = |s: Arc<str>| s.to_string().into_boxed_str();
+        let flag = value.flag_emoji().into_boxed_str();
 
-        SerializableCountry::new(to_box(value.code), to_box(value.name), value.coordinates)
+        SerializableCountry::new(
+            to_box(value.code),
+            to_box(value.name),
+            flag,
+            value.coordinates,
+            value.timezones,
+            value.calling_code,
+        )
     }
 }
 
@@ -56,7 +319,14 @@ impl From<&Country> for SerializableCountry {
     fn from(value: &Country) -> Self {
         let to_box = |s: &Arc<str>| s.clone().to_string().into_boxed_str();
 
-        SerializableCountry::new(to_box(&value.code), to_box(&value.name), value.coordinates)
+        SerializableCountry::new(
+            to_box(&value.code),
+            to_box(&value.name),
+            value.flag_emoji().into_boxed_str(),
+            value.coordinates,
+            value.timezones.clone(),
+            value.calling_code.clone(),
+        )
     }
 }
 
@@ -18,13 +18,25 @@
 
 use crate::{
     country_list::{get_countries, Country},
-    IpAddrEntry, IpAddrMap,
+    location::{Location, LocationColumns},
+    parse::{
+        count_rows, find_country, initial_capacity, warn_if_empty, warn_if_len_mismatch,
+        ParseOptions, ParseReport, RowErrors,
+    },
+    Error, IpAddrEntry, IpAddrMap,
 };
+use csv::StringRecord;
 use serde::{
     de::{Unexpected, Visitor},
     Deserialize, Deserializer,
 };
-use std::{fs, net::Ipv4Addr, path::Path, str::FromStr};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::Ipv4Addr,
+    path::Path,
+    str::FromStr,
+};
 
 /// Stores a range of IPv4 addresses and a value.
 ///
@@ -47,9 +59,95 @@ use std::{fs, net::Ipv4Addr, path::Path, str::FromStr};
 /// ```
 pub type Ipv4AddrEntry<T> = IpAddrEntry<Ipv4Addr, T>;
 
+impl<T: PartialEq> IpAddrMap<Ipv4Addr, T> {
+    /// For a given IPv4 address as a `u32`, find the value of the stored entry that contains it.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let start = Ipv4Addr::new(1, 1, 1, 1);
+    /// let end = Ipv4Addr::new(3, 3, 3, 3);
+    /// let middle = Ipv4Addr::new(2, 2, 2, 2);
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(start, end, "a").unwrap();
+    ///
+    /// assert_eq!(map.search_bits(u32::from(middle)).unwrap(), &"a");
+    /// ```
+    pub fn search_bits(&self, bits: u32) -> Result<&T, Error> {
+        self.try_search(Ipv4Addr::from_bits(bits))
+    }
+}
+
+impl<T: FromStr> FromStr for Ipv4AddrEntry<T> {
+    type Err = Error;
+
+    /// Parse a single database line in the format of `start,end,value`.
+    ///
+    /// `start` and `end` may each be a `u32` (ex. `16843009`) or dotted-quad (ex. `1.1.1.1`), and
+    /// `value` is parsed with its own `FromStr` implementation. Whitespace around each field is
+    /// ignored.
+    ///
+    /// Returns `Error::MalformedEntry` if the line doesn't have three fields or a field can't be
+    /// parsed, or `Error::EmptyRangeError` if `start` is after `end`.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{ipv4::Ipv4AddrEntry, Error};
+    ///
+    /// let entry: Ipv4AddrEntry<String> = "16843009,3.3.3.3,BE".parse().unwrap();
+    ///
+    /// assert_eq!(*entry.start(), Ipv4Addr::new(1, 1, 1, 1));
+    /// assert_eq!(*entry.end(), Ipv4Addr::new(3, 3, 3, 3));
+    /// assert_eq!(entry.value(), "BE");
+    ///
+    /// assert!(matches!(
+    ///     "1.1.1.1,BE".parse::<Ipv4AddrEntry<String>>(),
+    ///     Err(Error::MalformedEntry(_))
+    /// ));
+    /// assert!(matches!(
+    ///     "3.3.3.3,1.1.1.1,BE".parse::<Ipv4AddrEntry<String>>(),
+    ///     Err(Error::EmptyRangeError)
+    /// ));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || Error::MalformedEntry(s.into());
+
+        let mut fields = s.splitn(3, ',').map(str::trim);
+        let (Some(start), Some(end), Some(value)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(malformed());
+        };
+
+        let start = parse_ipv4(start).ok_or_else(malformed)?;
+        let end = parse_ipv4(end).ok_or_else(malformed)?;
+        let value = T::from_str(value).map_err(|_| malformed())?;
+
+        Self::new(start, end, value)
+    }
+}
+
 /// For given IPv4 database file of a given length, parse it into an `IpAddrMap` holding IPv4 addresses.
 ///
-/// `comment` is used internally as a `u8` by taking the last byte of `comment` (`comment as u8`).
+/// Returns the map alongside a `ParseReport` summarizing which rows were inserted or skipped.
+///
+/// `options` controls comment handling, whitespace trimming, blank line handling, header rows,
+/// which columns hold each field, whether ranges of unknown countries are kept, and whether each
+/// unrecognized country code is logged.
+///
+/// Rows that can't be parsed are skipped and logged as a warning. If more than half of the rows
+/// can't be parsed, returns `Error::WrongDatabaseFormat`, as the file is most likely not an IPv4
+/// database.
+///
+/// `len` is the expected number of ranges, used to size the map. If it is far from the number of
+/// ranges inserted, a warning suggesting the correct length is logged.
 ///
 /// Example usage:
 ///
@@ -83,7 +181,13 @@ pub type Ipv4AddrEntry<T> = IpAddrEntry<Ipv4Addr, T>;
 /// let path = temp_file.path().into();
 /// let len = 2;
 ///
-/// let mut ipv4_map = ip_geo::ipv4::parse_ipv4_file(path, len, Some('#'));
+/// let options = ip_geo::parse::ParseOptions {
+///     comment: Some(b'#'),
+///     ..Default::default()
+/// };
+///
+/// let (mut ipv4_map, report) = ip_geo::ipv4::parse_ipv4_file(path, len, options).unwrap();
+/// assert_eq!(report.inserted, 2);
 ///
 /// assert_eq!(ipv4_map.search(middle_a).unwrap().code, value_a);
 /// assert_eq!(ipv4_map.search(middle_b).unwrap().code, value_b);
@@ -94,8 +198,142 @@ pub type Ipv4AddrEntry<T> = IpAddrEntry<Ipv4Addr, T>;
 pub fn parse_ipv4_file(
     path: Box<Path>,
     len: usize,
-    comment: Option<char>,
-) -> IpAddrMap<Ipv4Addr, Country> {
+    options: ParseOptions,
+) -> Result<(IpAddrMap<Ipv4Addr, Country>, ParseReport), Error> {
+    let file = fs::File::open(&path)
+        .unwrap_or_else(|_| panic!("Could not open IPv4 database at {}", path.to_string_lossy()));
+    let mut map = IpAddrMap::new_with_capacity(initial_capacity(&file, len));
+
+    let report = parse_ipv4_into(&mut map, file, options, |_, country| country)?;
+    warn_if_empty(Some(&path), &report);
+    warn_if_len_mismatch(&path, len, &report);
+    map.cleanup();
+
+    Ok((map, report))
+}
+
+/// For a given in-memory IPv4 database, parse it into an `IpAddrMap` holding IPv4 addresses.
+///
+/// Like `parse_ipv4_file`, but reads from `data` instead of a file, ex. a database embedded with
+/// `include_bytes!`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::Ipv4Addr;
+/// use ip_geo::{ipv4::parse_ipv4_bytes, parse::ParseOptions};
+///
+/// let data = b"16777216,16777471,BE\n2.0.0.0,2.0.0.255,CA\n";
+///
+/// let (mut ipv4_map, report) = parse_ipv4_bytes(data, ParseOptions::default()).unwrap();
+/// assert_eq!(report.inserted, 2);
+///
+/// assert_eq!(ipv4_map.search(Ipv4Addr::new(2, 0, 0, 1)).unwrap().code, "CA".into());
+/// ```
+pub fn parse_ipv4_bytes(
+    data: &[u8],
+    options: ParseOptions,
+) -> Result<(IpAddrMap<Ipv4Addr, Country>, ParseReport), Error> {
+    let mut map = IpAddrMap::new_with_capacity(count_rows(data));
+
+    let report = parse_ipv4_into(&mut map, data, options, |_, country| country)?;
+    warn_if_empty(None, &report);
+    map.cleanup();
+
+    Ok((map, report))
+}
+
+impl IpAddrMap<Ipv4Addr, Country> {
+    /// Parse the IPv4 database file at `path`, inserting its ranges into this map, ex. to combine
+    /// the shards of a database split across several files.
+    ///
+    /// Parses like `parse_ipv4_file`, returning the `ParseReport` of this file alone, but leaves
+    /// the map unclean, so that it can be cleaned once after every file is appended. If an error
+    /// is returned, the rows parsed before it remain in the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file at `path` can't be opened.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::{io::Write, net::Ipv4Addr};
+    /// use ip_geo::{country_list::Country, parse::ParseOptions, IpAddrMap};
+    ///
+    /// let mut shard_a = tempfile::NamedTempFile::new().unwrap();
+    /// write!(shard_a, "1.0.0.0,1.0.0.255,BE\n").unwrap();
+    /// let mut shard_b = tempfile::NamedTempFile::new().unwrap();
+    /// write!(shard_b, "2.0.0.0,2.0.0.255,CA\n").unwrap();
+    ///
+    /// let mut map = IpAddrMap::<Ipv4Addr, Country>::new();
+    /// for shard in [&shard_a, &shard_b] {
+    ///     map.append_from_file(shard.path(), ParseOptions::default()).unwrap();
+    /// }
+    /// map.cleanup();
+    ///
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(map.search(Ipv4Addr::new(2, 0, 0, 1)).unwrap().code, "CA".into());
+    /// ```
+    pub fn append_from_file(
+        &mut self,
+        path: &Path,
+        options: ParseOptions,
+    ) -> Result<ParseReport, Error> {
+        let file = fs::File::open(path).unwrap_or_else(|_| {
+            panic!("Could not open IPv4 database at {}", path.to_string_lossy())
+        });
+
+        let report = parse_ipv4_into(self, file, options, |_, country| country)?;
+        warn_if_empty(Some(path), &report);
+
+        Ok(report)
+    }
+
+    /// Write this map to `writer` in the Tor CSV format, one row per line as `start,end,code`, with
+    /// the addresses as `u32`s, ex. `16777216,16777471,BE`, so that it can be parsed again with
+    /// `parse_ipv4_file`.
+    ///
+    /// Entries are written sorted; overlapping ranges are written as-is.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{ipv4::parse_ipv4_bytes, parse::ParseOptions};
+    ///
+    /// let data = b"2.0.0.0,2.0.0.255,CA\n1.0.0.0,1.0.0.255,BE\n";
+    /// let (map, _) = parse_ipv4_bytes(data, ParseOptions::default()).unwrap();
+    ///
+    /// let mut csv = Vec::new();
+    /// map.write_tor_csv(&mut csv).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(csv.clone()).unwrap(),
+    ///     "16777216,16777471,BE\n33554432,33554687,CA\n",
+    /// );
+    ///
+    /// let (mut map, _) = parse_ipv4_bytes(&csv, ParseOptions::default()).unwrap();
+    /// assert_eq!(map.search(Ipv4Addr::new(2, 0, 0, 1)).unwrap().code, "CA".into());
+    /// ```
+    pub fn write_tor_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in self {
+            let (start, end) = (entry.start().to_bits(), entry.end().to_bits());
+            writeln!(writer, "{start},{end},{}", entry.value().code)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse an IPv4 database from `reader`, inserting its ranges into `map` without cleaning it.
+///
+/// `to_value` builds the value of each range from its row and recognized country.
+fn parse_ipv4_into<T: PartialEq>(
+    map: &mut IpAddrMap<Ipv4Addr, T>,
+    reader: impl Read,
+    options: ParseOptions,
+    mut to_value: impl FnMut(&StringRecord, Country) -> T,
+) -> Result<ParseReport, Error> {
     #[derive(Deserialize, Debug)]
     struct Schema {
         #[serde(deserialize_with = "deserialize_ipv4")]
@@ -107,39 +345,134 @@ pub fn parse_ipv4_file(
         country_code: Box<str>,
     }
 
-    let file = fs::File::open(&path)
-        .unwrap_or_else(|_| panic!("Could not open IPv4 database at {}", path.to_string_lossy()));
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .comment(comment.map(|c| c as u8))
-        .from_reader(file);
+    let mut reader = options.reader_builder().from_reader(reader);
 
-    let mut map = IpAddrMap::new_with_capacity(len);
     let countries = get_countries();
+    let mut row_errors = RowErrors::default();
+    let mut report = ParseReport::default();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) if options.is_skipped(&record) => continue,
+            record => record,
+        };
 
-    for entry in reader.deserialize() {
-        let data: Schema = entry.unwrap();
+        report.total_rows += 1;
+
+        if let (1, Ok(record)) = (report.total_rows, &record) {
+            options.warn_if_header(record);
+        }
+
+        let data = record.and_then(|record| {
+            let data = options
+                .columns
+                .select(&record)
+                .deserialize::<Schema>(None)?;
+            Ok((data, record))
+        });
+        let Some((data, record)) = row_errors.record(data) else {
+            report.skipped_malformed += 1;
+            continue;
+        };
 
         let code = data.country_code.as_ref();
 
         // Ensure that it is a recognized country
-        match countries.get(code).cloned() {
+        match find_country(&countries, code) {
             Some(country) => {
-                // Only add ranges with associated countries
-                if country.code != "??".into() {
-                    map.insert(Ipv4AddrEntry::new(data.start, data.end, country).unwrap());
+                // Only add ranges with associated countries, unless asked to keep unknown ones
+                if country.code != "??".into() || options.keep_unknown {
+                    map.insert_range(data.start, data.end, to_value(&record, country))?;
+                    report.inserted += 1;
+                } else {
+                    report.skipped_placeholder += 1;
                 }
             }
-            None => eprintln!("Unrecognized country or region '{}'!", data.country_code),
+            None => {
+                options.warn_unknown_code(code);
+                report.skipped_unknown_code += 1;
+            }
         }
     }
 
+    row_errors.check()?;
+    options.warn_unknown_codes(&report);
+
+    Ok(report)
+}
+
+/// For a given IPv4 city database file of a given length, parse it into an `IpAddrMap` holding
+/// IPv4 addresses and the `Location` of each range.
+///
+/// Like `parse_ipv4_file`, but also reads the subdivision and city of each range from the columns
+/// given by `location_columns`, ex. `start,end,country_code,subdivision,city` by default. Empty or
+/// missing subdivision and city columns are left unknown.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{io::Write, net::Ipv4Addr};
+/// use ip_geo::{location::LocationColumns, parse::ParseOptions};
+///
+/// let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+/// write!(temp_file, "1.0.0.0,1.0.0.255,BE,BRU,Brussels\n2.0.0.0,2.0.0.255,CA,,\n").unwrap();
+/// let path = temp_file.path().into();
+///
+/// let (mut ipv4_map, report) = ip_geo::ipv4::parse_ipv4_city_file(
+///     path,
+///     2,
+///     ParseOptions::default(),
+///     LocationColumns::default(),
+/// )
+/// .unwrap();
+/// assert_eq!(report.inserted, 2);
+///
+/// let brussels = ipv4_map.search(Ipv4Addr::new(1, 0, 0, 1)).unwrap();
+/// assert_eq!(brussels.country.code, "BE".into());
+/// assert_eq!(brussels.subdivision.as_deref(), Some("BRU"));
+/// assert_eq!(brussels.city.as_deref(), Some("Brussels"));
+///
+/// let canada = ipv4_map.search(Ipv4Addr::new(2, 0, 0, 1)).unwrap();
+/// assert_eq!(canada.subdivision, None);
+/// assert_eq!(canada.city, None);
+/// ```
+pub fn parse_ipv4_city_file(
+    path: Box<Path>,
+    len: usize,
+    options: ParseOptions,
+    location_columns: LocationColumns,
+) -> Result<(IpAddrMap<Ipv4Addr, Location>, ParseReport), Error> {
+    /// Returns `None` for an empty or missing field.
+    fn non_empty(field: Option<&str>) -> Option<Box<str>> {
+        field
+            .filter(|field| !field.trim().is_empty())
+            .map(Box::from)
+    }
+
+    let file = fs::File::open(&path).unwrap_or_else(|_| {
+        panic!(
+            "Could not open IPv4 city database at {}",
+            path.to_string_lossy()
+        )
+    });
+    let mut map = IpAddrMap::new_with_capacity(initial_capacity(&file, len));
+
+    let report = parse_ipv4_into(&mut map, file, options, |record, country| Location {
+        country,
+        subdivision: non_empty(record.get(location_columns.subdivision)),
+        city: non_empty(record.get(location_columns.city)),
+    })?;
+    warn_if_empty(Some(&path), &report);
+    warn_if_len_mismatch(&path, len, &report);
     map.cleanup();
 
-    map
+    Ok((map, report))
 }
 
-/// Serde deserializer to convert a `u32` into an `Ipv4Addr`.
+/// Serde deserializer to convert a `u32` or a dotted-quad string into an `Ipv4Addr`.
+///
+/// Accepts both the integer form used by Tor (ex. `16843009`) and the dotted-quad form used by
+/// MaxMind (ex. `1.1.1.1`), even within the same file.
 fn deserialize_ipv4<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Ipv4Addr, D::Error> {
     pub struct Ipv4Deserializer;
 
@@ -161,9 +494,44 @@ fn deserialize_ipv4<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Ipv4Ad
         where
             E: serde::de::Error,
         {
-            Ipv4Addr::from_str(v).map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+            parse_ipv4(v).ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
         }
     }
 
-    deserializer.deserialize_u32(Ipv4Deserializer)
+    deserializer.deserialize_str(Ipv4Deserializer)
+}
+
+/// Parse a `u32` (ex. `16843009`) or a dotted-quad string (ex. `1.1.1.1`) into an `Ipv4Addr`.
+fn parse_ipv4(s: &str) -> Option<Ipv4Addr> {
+    if let Ok(bits) = u32::from_str(s) {
+        return Some(Ipv4Addr::from_bits(bits));
+    }
+
+    Ipv4Addr::from_str(s).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ipv4_file_mixed_formats() {
+        let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            temp_file,
+            "{},{},BE\n4.4.4.4,6.6.6.6,CA\n",
+            u32::from(Ipv4Addr::new(1, 1, 1, 1)),
+            u32::from(Ipv4Addr::new(3, 3, 3, 3)),
+        )
+        .unwrap();
+        let path = temp_file.path().into();
+
+        let (map, report) = parse_ipv4_file(path, 2, ParseOptions::default()).unwrap();
+        assert_eq!(report.inserted, 2);
+
+        let get_code = |addr| map.try_search(addr).unwrap().code.clone();
+
+        assert_eq!(get_code(Ipv4Addr::new(2, 2, 2, 2)), "BE".into());
+        assert_eq!(get_code(Ipv4Addr::new(5, 5, 5, 5)), "CA".into());
+    }
 }
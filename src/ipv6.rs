@@ -18,13 +18,23 @@
 
 use crate::{
     country_list::{get_countries, Country},
-    IpAddrEntry, IpAddrMap,
+    parse::{
+        count_rows, find_country, initial_capacity, warn_if_empty, warn_if_len_mismatch,
+        ParseOptions, ParseReport, RowErrors,
+    },
+    Error, IpAddrEntry, IpAddrMap,
 };
 use serde::{
     de::{Unexpected, Visitor},
     Deserialize, Deserializer,
 };
-use std::{fs, net::Ipv6Addr, path::Path, str::FromStr};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::Ipv6Addr,
+    path::Path,
+    str::FromStr,
+};
 
 /// Stores a range of IPv6 addresses and a value.
 ///
@@ -47,9 +57,45 @@ use std::{fs, net::Ipv6Addr, path::Path, str::FromStr};
 /// ```
 pub type Ipv6AddrEntry<T> = IpAddrEntry<Ipv6Addr, T>;
 
+impl<T: PartialEq> IpAddrMap<Ipv6Addr, T> {
+    /// For a given IPv6 address as a `u128`, find the value of the stored entry that contains it.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv6Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let start = Ipv6Addr::new(1, 0, 0, 0, 0, 0, 0, 0);
+    /// let end = Ipv6Addr::new(3, 0, 0, 0, 0, 0, 0, 0);
+    /// let middle = Ipv6Addr::new(2, 0, 0, 0, 0, 0, 0, 0);
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(start, end, "a").unwrap();
+    ///
+    /// assert_eq!(map.search_bits(u128::from(middle)).unwrap(), &"a");
+    /// ```
+    pub fn search_bits(&self, bits: u128) -> Result<&T, Error> {
+        self.try_search(Ipv6Addr::from_bits(bits))
+    }
+}
+
 /// For given IPv6 database file of a given length, parse it into an `IpAddrMap` holding IPv6 addresses.
 ///
-/// `comment` is used internally as a `u8` by taking the last byte of `comment` (`comment as u8`).
+/// Returns the map alongside a `ParseReport` summarizing which rows were inserted or skipped.
+///
+/// `options` controls comment handling, whitespace trimming, blank line handling, header rows,
+/// which columns hold each field, whether ranges of unknown countries are kept, and whether each
+/// unrecognized country code is logged.
+///
+/// Rows that can't be parsed are skipped and logged as a warning. If more than half of the rows
+/// can't be parsed, returns `Error::WrongDatabaseFormat`, as the file is most likely not an IPv6
+/// database.
+///
+/// `len` is the expected number of ranges, used to size the map. If it is far from the number of
+/// ranges inserted, a warning suggesting the correct length is logged.
 ///
 /// Example usage:
 ///
@@ -80,7 +126,13 @@ pub type Ipv6AddrEntry<T> = IpAddrEntry<Ipv6Addr, T>;
 /// let path = temp_file.path().into();
 /// let len = 2;
 ///
-/// let mut ipv6_map = ip_geo::ipv6::parse_ipv6_file(path, len, Some('#'));
+/// let options = ip_geo::parse::ParseOptions {
+///     comment: Some(b'#'),
+///     ..Default::default()
+/// };
+///
+/// let (mut ipv6_map, report) = ip_geo::ipv6::parse_ipv6_file(path, len, options).unwrap();
+/// assert_eq!(report.inserted, 2);
 ///
 /// assert_eq!(ipv6_map.search(middle_a).unwrap().code, value_a);
 /// assert_eq!(ipv6_map.search(middle_b).unwrap().code, value_b);
@@ -91,8 +143,137 @@ pub type Ipv6AddrEntry<T> = IpAddrEntry<Ipv6Addr, T>;
 pub fn parse_ipv6_file(
     path: Box<Path>,
     len: usize,
-    comment: Option<char>,
-) -> IpAddrMap<Ipv6Addr, Country> {
+    options: ParseOptions,
+) -> Result<(IpAddrMap<Ipv6Addr, Country>, ParseReport), Error> {
+    let file = fs::File::open(&path)
+        .unwrap_or_else(|_| panic!("Could not open IPv6 database at {}", path.to_string_lossy()));
+    let mut map = IpAddrMap::new_with_capacity(initial_capacity(&file, len));
+
+    let report = parse_ipv6_into(&mut map, file, options)?;
+    warn_if_empty(Some(&path), &report);
+    warn_if_len_mismatch(&path, len, &report);
+    map.cleanup();
+
+    Ok((map, report))
+}
+
+/// For a given in-memory IPv6 database, parse it into an `IpAddrMap` holding IPv6 addresses.
+///
+/// Like `parse_ipv6_file`, but reads from `data` instead of a file, ex. a database embedded with
+/// `include_bytes!`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::Ipv6Addr;
+/// use ip_geo::{ipv6::parse_ipv6_bytes, parse::ParseOptions};
+///
+/// let data = b"1::,1::ffff,BE\n2::,2::ffff,CA\n";
+///
+/// let (mut ipv6_map, report) = parse_ipv6_bytes(data, ParseOptions::default()).unwrap();
+/// assert_eq!(report.inserted, 2);
+///
+/// let canada = ipv6_map.search(Ipv6Addr::new(2, 0, 0, 0, 0, 0, 0, 1)).unwrap();
+/// assert_eq!(canada.code, "CA".into());
+/// ```
+pub fn parse_ipv6_bytes(
+    data: &[u8],
+    options: ParseOptions,
+) -> Result<(IpAddrMap<Ipv6Addr, Country>, ParseReport), Error> {
+    let mut map = IpAddrMap::new_with_capacity(count_rows(data));
+
+    let report = parse_ipv6_into(&mut map, data, options)?;
+    warn_if_empty(None, &report);
+    map.cleanup();
+
+    Ok((map, report))
+}
+
+impl IpAddrMap<Ipv6Addr, Country> {
+    /// Parse the IPv6 database file at `path`, inserting its ranges into this map, ex. to combine
+    /// the shards of a database split across several files.
+    ///
+    /// Parses like `parse_ipv6_file`, returning the `ParseReport` of this file alone, but leaves
+    /// the map unclean, so that it can be cleaned once after every file is appended. If an error
+    /// is returned, the rows parsed before it remain in the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file at `path` can't be opened.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::{io::Write, net::Ipv6Addr, str::FromStr};
+    /// use ip_geo::{country_list::Country, parse::ParseOptions, IpAddrMap};
+    ///
+    /// let mut shard_a = tempfile::NamedTempFile::new().unwrap();
+    /// write!(shard_a, "1::,1::ffff,BE\n").unwrap();
+    /// let mut shard_b = tempfile::NamedTempFile::new().unwrap();
+    /// write!(shard_b, "2::,2::ffff,CA\n").unwrap();
+    ///
+    /// let mut map = IpAddrMap::<Ipv6Addr, Country>::new();
+    /// for shard in [&shard_a, &shard_b] {
+    ///     map.append_from_file(shard.path(), ParseOptions::default()).unwrap();
+    /// }
+    /// map.cleanup();
+    ///
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(map.search(Ipv6Addr::from_str("2::1").unwrap()).unwrap().code, "CA".into());
+    /// ```
+    pub fn append_from_file(
+        &mut self,
+        path: &Path,
+        options: ParseOptions,
+    ) -> Result<ParseReport, Error> {
+        let file = fs::File::open(path).unwrap_or_else(|_| {
+            panic!("Could not open IPv6 database at {}", path.to_string_lossy())
+        });
+
+        let report = parse_ipv6_into(self, file, options)?;
+        warn_if_empty(Some(path), &report);
+
+        Ok(report)
+    }
+
+    /// Write this map to `writer` in the Tor CSV format, one row per line as `start,end,code`, with
+    /// the addresses in colon notation, ex. `1::,1::ffff,BE`, so that it can be parsed again with
+    /// `parse_ipv6_file`.
+    ///
+    /// Entries are written sorted; overlapping ranges are written as-is.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::{net::Ipv6Addr, str::FromStr};
+    /// use ip_geo::{ipv6::parse_ipv6_bytes, parse::ParseOptions};
+    ///
+    /// let data = b"2::,2::ffff,CA\n1::,1::ffff,BE\n";
+    /// let (map, _) = parse_ipv6_bytes(data, ParseOptions::default()).unwrap();
+    ///
+    /// let mut csv = Vec::new();
+    /// map.write_tor_csv(&mut csv).unwrap();
+    /// assert_eq!(String::from_utf8(csv.clone()).unwrap(), "1::,1::ffff,BE\n2::,2::ffff,CA\n");
+    ///
+    /// let (mut map, _) = parse_ipv6_bytes(&csv, ParseOptions::default()).unwrap();
+    /// assert_eq!(map.search(Ipv6Addr::from_str("2::1").unwrap()).unwrap().code, "CA".into());
+    /// ```
+    pub fn write_tor_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in self {
+            let (start, end) = (entry.start(), entry.end());
+            writeln!(writer, "{start},{end},{}", entry.value().code)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse an IPv6 database from `reader`, inserting its ranges into `map` without cleaning it.
+fn parse_ipv6_into(
+    map: &mut IpAddrMap<Ipv6Addr, Country>,
+    reader: impl Read,
+    options: ParseOptions,
+) -> Result<ParseReport, Error> {
     #[derive(Deserialize, Debug)]
     struct Schema {
         #[serde(deserialize_with = "deserialize_ipv6")]
@@ -104,36 +285,78 @@ pub fn parse_ipv6_file(
         country_code: Box<str>,
     }
 
-    let file = fs::File::open(&path)
-        .unwrap_or_else(|_| panic!("Could not open IPv6 database at {}", path.to_string_lossy()));
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .comment(comment.map(|c| c as u8))
-        .from_reader(file);
+    #[derive(Deserialize, Debug)]
+    struct NetworkSchema {
+        #[serde(deserialize_with = "deserialize_ipv6_network")]
+        network: (Ipv6Addr, Ipv6Addr),
+
+        country_code: Box<str>,
+    }
+
+    let mut reader = options.reader_builder().from_reader(reader);
 
-    let mut map = IpAddrMap::new_with_capacity(len);
     let countries = get_countries();
+    let mut row_errors = RowErrors::default();
+    let mut report = ParseReport::default();
 
-    for entry in reader.deserialize() {
-        let data: Schema = entry.unwrap();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) if options.is_skipped(&record) => continue,
+            record => record,
+        };
+
+        report.total_rows += 1;
+
+        if let (1, Ok(record)) = (report.total_rows, &record) {
+            options.warn_if_header(record);
+        }
+
+        let data = record.and_then(|record| {
+            let selected = options.columns.select(&record);
+
+            match options.columns.network {
+                Some(_) => selected.deserialize::<NetworkSchema>(None).map(|data| {
+                    let (start, end) = data.network;
+                    let country_code = data.country_code;
+
+                    Schema {
+                        start,
+                        end,
+                        country_code,
+                    }
+                }),
+                None => selected.deserialize::<Schema>(None),
+            }
+        });
+        let Some(data) = row_errors.record(data) else {
+            report.skipped_malformed += 1;
+            continue;
+        };
 
         let code = data.country_code.as_ref();
 
         // Ensure that it is a recognized country
-        match countries.get(code).cloned() {
+        match find_country(&countries, code) {
             Some(country) => {
-                // Only add ranges with associated countries
-                if country.code != "??".into() {
-                    map.insert(Ipv6AddrEntry::new(data.start, data.end, country).unwrap());
+                // Only add ranges with associated countries, unless asked to keep unknown ones
+                if country.code != "??".into() || options.keep_unknown {
+                    map.insert_range(data.start, data.end, country)?;
+                    report.inserted += 1;
+                } else {
+                    report.skipped_placeholder += 1;
                 }
             }
-            None => eprintln!("Unrecognized country or region '{}'!", data.country_code),
+            None => {
+                options.warn_unknown_code(code);
+                report.skipped_unknown_code += 1;
+            }
         }
     }
 
-    map.cleanup();
+    row_errors.check()?;
+    options.warn_unknown_codes(&report);
 
-    map
+    Ok(report)
 }
 
 /// Serde deserializer to convert a `u128` into an `Ipv6Addr`.
@@ -164,3 +387,73 @@ fn deserialize_ipv6<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Ipv6Ad
 
     deserializer.deserialize_str(Ipv6Deserializer)
 }
+
+/// Serde deserializer to convert a network in CIDR notation into its first and last addresses.
+fn deserialize_ipv6_network<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<(Ipv6Addr, Ipv6Addr), D::Error> {
+    let network = Box::<str>::deserialize(deserializer)?;
+
+    parse_ipv6_network(&network).ok_or_else(|| {
+        serde::de::Error::invalid_value(
+            Unexpected::Str(&network),
+            &"an IPv6 network in CIDR notation",
+        )
+    })
+}
+
+/// Parse an IPv6 network in CIDR notation (ex. `2001:db8::/32`) into its first and last
+/// addresses.
+///
+/// Host bits set in the address are ignored, ex. `2001:db8::1/32` is the same network as
+/// `2001:db8::/32`. Returns `None` if the address or the prefix length is invalid.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{net::Ipv6Addr, str::FromStr};
+/// use ip_geo::ipv6::parse_ipv6_network;
+///
+/// let addr = |s| Ipv6Addr::from_str(s).unwrap();
+///
+/// assert_eq!(
+///     parse_ipv6_network("2001:db8::/32"),
+///     Some((addr("2001:db8::"), addr("2001:db8:ffff:ffff:ffff:ffff:ffff:ffff"))),
+/// );
+/// assert_eq!(
+///     parse_ipv6_network("2001:db8:1:2::/64"),
+///     Some((addr("2001:db8:1:2::"), addr("2001:db8:1:2:ffff:ffff:ffff:ffff"))),
+/// );
+/// assert_eq!(
+///     parse_ipv6_network("2001:db8:1::1/48"),
+///     Some((addr("2001:db8:1::"), addr("2001:db8:1:ffff:ffff:ffff:ffff:ffff"))),
+/// );
+///
+/// // A single address
+/// assert_eq!(parse_ipv6_network("::1/128"), Some((Ipv6Addr::LOCALHOST, Ipv6Addr::LOCALHOST)));
+///
+/// // The whole address space
+/// assert_eq!(
+///     parse_ipv6_network("::/0"),
+///     Some((Ipv6Addr::UNSPECIFIED, Ipv6Addr::from_bits(u128::MAX))),
+/// );
+///
+/// assert_eq!(parse_ipv6_network("2001:db8::/129"), None);
+/// assert_eq!(parse_ipv6_network("2001:db8::"), None);
+/// assert_eq!(parse_ipv6_network("1.1.1.0/24"), None);
+/// ```
+pub fn parse_ipv6_network(network: &str) -> Option<(Ipv6Addr, Ipv6Addr)> {
+    let (address, prefix) = network.trim().split_once('/')?;
+    let address = Ipv6Addr::from_str(address).ok()?;
+    let prefix = u32::from_str(prefix)
+        .ok()
+        .filter(|&prefix| prefix <= Ipv6Addr::BITS)?;
+
+    let host_mask = u128::MAX.checked_shr(prefix).unwrap_or(0);
+    let start = address.to_bits() & !host_mask;
+
+    Some((
+        Ipv6Addr::from_bits(start),
+        Ipv6Addr::from_bits(start | host_mask),
+    ))
+}
@@ -16,12 +16,40 @@
 // You should have received a copy of the GNU Affero General Public License along with ip_geo. If
 // not, see <https://www.gnu.org/licenses/>.
 
-use std::{cmp::Ordering, ops::RangeInclusive};
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use core::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Display,
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::{Deref, RangeInclusive},
+};
+
+#[cfg(feature = "std")]
+use std::{collections::HashMap, hash::Hash};
+
+use sync::{Mutex, OnceLock};
+
+#[cfg(feature = "std")]
+pub mod annotated;
+#[cfg(feature = "std")]
 pub mod country;
+#[cfg(feature = "std")]
 pub mod country_list;
+#[cfg(feature = "std")]
 pub mod ipv4;
+#[cfg(feature = "std")]
 pub mod ipv6;
+#[cfg(feature = "std")]
+pub mod location;
+#[cfg(feature = "std")]
+pub mod parse;
+mod sync;
 
 /// Stores a searchable list of `IpAddrEntries`.
 ///
@@ -55,111 +83,1607 @@ pub mod ipv6;
 /// assert_eq!(map.get_from_index_as_ref(0).unwrap(), &entry_a);
 /// assert_eq!(map.get_from_index_as_ref(1).unwrap(), &entry_b);
 /// ```
+///
+/// The map is cleaned (sorted and deduplicated) lazily, by the first operation that needs it. This
+/// happens at most once between inserts, even through a shared reference, so a `&IpAddrMap` can be
+/// searched from multiple threads at once:
+///
+/// ```rust
+/// use std::{net::Ipv4Addr, thread};
+/// use ip_geo::IpAddrMap;
+///
+/// let mut map = IpAddrMap::new();
+/// map.insert_range(Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b")
+///     .unwrap();
+/// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+///     .unwrap();
+///
+/// let map = &map;
+/// thread::scope(|scope| {
+///     scope.spawn(|| assert_eq!(map.try_search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &"a"));
+///     scope.spawn(|| assert_eq!(map.try_search(Ipv4Addr::new(5, 5, 5, 5)).unwrap(), &"b"));
+/// });
+/// ```
+///
+/// Without the `std` feature, the map is not `Sync`, as there is no `Mutex` to clean it with.
 #[derive(Debug)]
 pub struct IpAddrMap<A: Ord + Copy, T: PartialEq> {
-    inner: Vec<IpAddrEntry<A, T>>,
-    dirty: bool,
+    /// Entries inserted since the map was last cleaned.
+    dirty: Mutex<Vec<IpAddrEntry<A, T>>>,
+    /// The sorted and deduplicated entries, once the map has been cleaned.
+    clean: OnceLock<Vec<IpAddrEntry<A, T>>>,
 }
 
 impl<A: Ord + Copy, T: PartialEq> IpAddrMap<A, T> {
     /// Create a new, unsized instance of `Self`.
     pub const fn new() -> Self {
         Self {
-            inner: vec![],
-            dirty: false,
+            dirty: Mutex::new(Vec::new()),
+            clean: OnceLock::new(),
+        }
+    }
+
+    /// Create a new instance of `Self` with a starting capacity for the internal `Vec`.
+    pub fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            dirty: Mutex::new(Vec::with_capacity(capacity)),
+            clean: OnceLock::new(),
+        }
+    }
+
+    /// Create a new instance of `Self` from entries that are already sorted and deduplicated.
+    fn from_clean(entries: Vec<IpAddrEntry<A, T>>) -> Self {
+        Self {
+            dirty: Mutex::default(),
+            clean: OnceLock::from(entries),
+        }
+    }
+
+    /// Return the sorted and deduplicated entries, cleaning the map first if necessary.
+    ///
+    /// If multiple threads call this at once, only one cleans the map while the others wait.
+    fn entries(&self) -> &[IpAddrEntry<A, T>] {
+        self.clean.get_or_init(|| {
+            let mut dirty = self.dirty.lock();
+            let mut entries = mem::take(&mut *dirty);
+            Self::sort_and_dedup_entries(&mut entries);
+
+            entries
+        })
+    }
+
+    /// Sort `entries` by IP address range and remove exact duplicates, returning how many were
+    /// removed.
+    fn sort_and_dedup_entries(entries: &mut Vec<IpAddrEntry<A, T>>) -> usize {
+        let len = entries.len();
+
+        entries.sort_unstable_by_key(|e| (e.start, e.end));
+        entries.dedup_by(|a, b| a == b);
+
+        len - entries.len()
+    }
+
+    /// Return the internal `Vec` currently holding the entries, whether or not the map is clean.
+    fn entries_mut(&mut self) -> &mut Vec<IpAddrEntry<A, T>> {
+        match self.clean.get_mut() {
+            Some(entries) => entries,
+            None => self.dirty.get_mut(),
+        }
+    }
+
+    /// Consume the map and return its sorted and deduplicated entries.
+    fn into_entries(self) -> Vec<IpAddrEntry<A, T>> {
+        self.entries();
+
+        self.clean.into_inner().unwrap_or_default()
+    }
+
+    /// Create a new, clean instance of `Self` from rows of IP address ranges and values, in the
+    /// form `(start, end, value)`.
+    ///
+    /// Returns `Error::EmptyRangeError` if any row has `start > end`.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let map = IpAddrMap::from_rows([
+    ///     (Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b"),
+    ///     (Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a"),
+    /// ])
+    /// .unwrap();
+    ///
+    /// assert_eq!(map.try_search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &"a");
+    /// assert_eq!(map.try_search(Ipv4Addr::new(5, 5, 5, 5)).unwrap(), &"b");
+    ///
+    /// assert!(IpAddrMap::from_rows([(Ipv4Addr::new(2, 2, 2, 2), Ipv4Addr::new(1, 1, 1, 1), "c")])
+    ///     .is_err());
+    /// ```
+    pub fn from_rows(rows: impl IntoIterator<Item = (A, A, T)>) -> Result<Self, Error> {
+        let rows = rows.into_iter();
+        let mut map = Self::new_with_capacity(rows.size_hint().0);
+
+        for (start, end, value) in rows {
+            map.insert_range(start, end, value)?;
+        }
+
+        map.cleanup();
+
+        Ok(map)
+    }
+
+    /// Add another entry into the map.
+    pub fn insert(&mut self, entry: IpAddrEntry<A, T>) {
+        let clean = self.clean.take();
+        let dirty = self.dirty.get_mut();
+
+        // Move the clean entries back, so that they are cleaned alongside the new entry
+        if let Some(mut clean) = clean {
+            clean.append(dirty);
+            *dirty = clean;
+        }
+
+        dirty.push(entry);
+    }
+
+    /// Add another entry into the map, constructed from a range of IP addresses and a value.
+    ///
+    /// Returns `Error::EmptyRangeError` if `start > end`, leaving the map unchanged.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{Error, IpAddrMap};
+    ///
+    /// let mut map = IpAddrMap::new();
+    ///
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    /// assert!(matches!(
+    ///     map.insert_range(Ipv4Addr::new(6, 6, 6, 6), Ipv4Addr::new(4, 4, 4, 4), "b"),
+    ///     Err(Error::EmptyRangeError)
+    /// ));
+    ///
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(map.search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &"a");
+    /// ```
+    pub fn insert_range(&mut self, start: A, end: A, value: T) -> Result<(), Error> {
+        self.insert(IpAddrEntry::new(start, end, value)?);
+
+        Ok(())
+    }
+
+    /// For a given IP address, find the value of the stored entries the contains it, else `None`.
+    ///
+    /// Cleans the map first, if necessary.
+    pub fn search(&mut self, address: A) -> Result<&T, Error> {
+        // Fill the clean view through `&mut self`, so that `try_search()` doesn't have to
+        // initialize the `OnceLock` itself.
+        self.cleanup();
+
+        self.try_search(address)
+    }
+
+    /// For a given IP address, find the value of the stored entries the contains it, else `None`.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    pub fn try_search(&self, address: A) -> Result<&T, Error> {
+        Ok(self.try_search_entry(address)?.value())
+    }
+
+    /// For a given IP address, find the value of the stored entries the contains it and return a
+    /// clone of it, else `Error::NoValueFound`.
+    ///
+    /// Useful for holding onto the value past the borrow of the map, such as when the map is
+    /// shared through an `Arc`.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::{net::Ipv4Addr, sync::Arc};
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), String::from("a"))
+    ///     .unwrap();
+    /// let map = Arc::new(map);
+    ///
+    /// let value: String = map.search_owned(Ipv4Addr::new(2, 2, 2, 2)).unwrap();
+    /// drop(map);
+    ///
+    /// assert_eq!(value, "a");
+    /// ```
+    pub fn search_owned(&self, address: A) -> Result<T, Error>
+    where
+        T: Clone,
+    {
+        self.try_search(address).cloned()
+    }
+
+    /// For a given IP address, find the value of the stored entries the contains it, else
+    /// `default`.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(map.try_search_or(Ipv4Addr::new(2, 2, 2, 2), &"b").unwrap(), &"a");
+    /// assert_eq!(map.try_search_or(Ipv4Addr::new(5, 5, 5, 5), &"b").unwrap(), &"b");
+    /// ```
+    pub fn try_search_or<'m>(&'m self, address: A, default: &'m T) -> Result<&'m T, Error> {
+        match self.try_search(address) {
+            Err(Error::NoValueFound) => Ok(default),
+            result => result,
+        }
+    }
+
+    /// For a given IP address, find the stored entry that contains it, else `None`.
+    ///
+    /// Cleans the map first, if necessary.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{IpAddrEntry, IpAddrMap};
+    ///
+    /// let entry = IpAddrEntry::new(
+    ///     Ipv4Addr::new(1, 1, 1, 1),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     "a",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert(entry.clone());
+    ///
+    /// assert_eq!(map.search_entry(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &entry);
+    /// ```
+    pub fn search_entry(&mut self, address: A) -> Result<&IpAddrEntry<A, T>, Error> {
+        self.cleanup();
+
+        self.try_search_entry(address)
+    }
+
+    /// For a given IP address, find the stored entry that contains it, else `None`.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    pub fn try_search_entry(&self, address: A) -> Result<&IpAddrEntry<A, T>, Error> {
+        let entries = self.entries();
+
+        let index = entries
+            .binary_search_by(|e| e.cmp_address(&address))
+            .map_err(|_| Error::NoValueFound)?;
+
+        // Safety: `binary_search_by` would already have returned an error if the index didn't exist
+        Ok(&entries[index])
+    }
+
+    /// For a given IP address, return every stored entry that contains it, in sorted order.
+    ///
+    /// Unlike `try_search_entry()`, which returns an arbitrary match, this is correct for layered
+    /// databases with intentionally overlapping ranges (ex. a country range containing a more
+    /// specific city range). Returns an empty `Vec` if no entry contains the address.
+    ///
+    /// Because a long range can start arbitrarily far before the address, this scans every entry
+    /// that starts at or before it, so prefer `try_search()` for maps without overlapping ranges,
+    /// and `IntervalMap::search_all()` for maps with many.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 255, 255, 255), "country")
+    ///     .unwrap();
+    /// map.insert_range(Ipv4Addr::new(1, 2, 0, 0), Ipv4Addr::new(1, 2, 255, 255), "city")
+    ///     .unwrap();
+    ///
+    /// let values = |address| -> Vec<&str> {
+    ///     map.search_all(address).into_iter().map(|e| *e.value()).collect()
+    /// };
+    ///
+    /// assert_eq!(values(Ipv4Addr::new(1, 2, 3, 4)), ["country", "city"]);
+    /// assert_eq!(values(Ipv4Addr::new(1, 3, 0, 0)), ["country"]);
+    /// assert!(values(Ipv4Addr::new(2, 0, 0, 0)).is_empty());
+    /// ```
+    pub fn search_all(&self, address: A) -> Vec<&IpAddrEntry<A, T>> {
+        let entries = self.entries();
+
+        // One past the last entry that begins at or before `address`
+        let last = entries.partition_point(|e| e.start <= address);
+
+        entries[..last]
+            .iter()
+            .filter(|e| e.end >= address)
+            .collect()
+    }
+
+    /// For a given range of IP addresses, find every stored entry that overlaps it.
+    ///
+    /// Returns an empty slice if no entries overlap the range, or `Error::EmptyRangeError` if
+    /// `start > end`.
+    ///
+    /// Cleans the map first, if necessary. Like the other searches, assumes that the stored entries
+    /// do not overlap each other.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 0, 255, 255), "a")
+    ///     .unwrap();
+    /// map.insert_range(Ipv4Addr::new(10, 1, 0, 0), Ipv4Addr::new(10, 1, 255, 255), "b")
+    ///     .unwrap();
+    /// map.insert_range(Ipv4Addr::new(11, 0, 0, 0), Ipv4Addr::new(11, 0, 0, 255), "c")
+    ///     .unwrap();
+    ///
+    /// let entries = map
+    ///     .search_range(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 255, 255, 255))
+    ///     .unwrap();
+    /// let values: Vec<_> = entries.iter().map(|entry| *entry.value()).collect();
+    /// assert_eq!(values, ["a", "b"]);
+    ///
+    /// let entries = map
+    ///     .search_range(Ipv4Addr::new(12, 0, 0, 0), Ipv4Addr::new(12, 255, 255, 255))
+    ///     .unwrap();
+    /// assert!(entries.is_empty());
+    /// ```
+    pub fn search_range(&self, start: A, end: A) -> Result<&[IpAddrEntry<A, T>], Error> {
+        if start > end {
+            return Err(Error::EmptyRangeError);
+        }
+
+        let entries = self.entries();
+
+        // The first entry that ends at or after `start`
+        let first = entries.partition_point(|e| e.end < start);
+        // One past the last entry that begins at or before `end`
+        let last = entries.partition_point(|e| e.start <= end);
+
+        Ok(&entries[first..last.max(first)])
+    }
+
+    /// For each of a batch of IP addresses sorted in ascending order, find the value of the stored
+    /// entry that contains it, else `None`.
+    ///
+    /// Walks the batch and the stored entries together once, rather than searching the whole map
+    /// for each address, which is faster for large batches.
+    ///
+    /// If `sorted_addrs` is not sorted, addresses after one that is out of order may not be found.
+    /// In debug builds, this panics instead.
+    ///
+    /// Cleans the map first, if necessary.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    /// map.insert_range(Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b")
+    ///     .unwrap();
+    ///
+    /// let addresses = [
+    ///     Ipv4Addr::new(0, 0, 0, 0),
+    ///     Ipv4Addr::new(2, 2, 2, 2),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     Ipv4Addr::new(5, 5, 5, 5),
+    ///     Ipv4Addr::new(7, 7, 7, 7),
+    /// ];
+    ///
+    /// assert_eq!(
+    ///     map.search_sorted_batch(&addresses),
+    ///     [None, Some(&"a"), Some(&"a"), Some(&"b"), None]
+    /// );
+    /// ```
+    pub fn search_sorted_batch(&self, sorted_addrs: &[A]) -> Vec<Option<&T>> {
+        debug_assert!(
+            sorted_addrs.windows(2).all(|pair| pair[0] <= pair[1]),
+            "addresses must be sorted in ascending order"
+        );
+
+        let entries = self.entries();
+        let mut index = 0;
+
+        sorted_addrs
+            .iter()
+            .map(|address| {
+                let remaining = &entries[index..];
+
+                // Gallop forward until past the first entry that ends at or after `address`, then
+                // search for it within the entries skipped by the last step
+                let mut bound = 1;
+                while bound < remaining.len() && remaining[bound].end < *address {
+                    bound *= 2;
+                }
+                let bound = bound.min(remaining.len());
+                index += remaining[..bound].partition_point(|e| e.end < *address);
+
+                entries
+                    .get(index)
+                    .filter(|e| e.start <= *address)
+                    .map(IpAddrEntry::value)
+            })
+            .collect()
+    }
+
+    /// If necessary, prepare internal `Vec` for searching by performing a sort, dedup, and shrink.
+    ///
+    /// This is called by `Self::search()`, it should not be necessary to perform manually unless
+    /// it is used in an interactive program and you want to do as much work as possible before interactivity.
+    ///
+    /// The shrink assumes that you are done adding to the map. If the map will be added to and
+    /// searched repeatedly (ex. while reloading a database), use `Self::sort_and_dedup()` instead,
+    /// to avoid freeing capacity that will be needed again.
+    pub fn cleanup(&mut self) {
+        self.sort_and_dedup();
+
+        if let Some(entries) = self.clean.get_mut() {
+            entries.shrink_to_fit();
+        }
+    }
+
+    /// Like `Self::cleanup()`, but also return how many duplicate entries were removed and how many
+    /// entries remain.
+    ///
+    /// If the map is already clean, no duplicates are removed.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{CleanupStats, IpAddrMap};
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    /// map.insert_range(Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     map.cleanup_report(),
+    ///     CleanupStats {
+    ///         removed_duplicates: 1,
+    ///         remaining: 2,
+    ///     }
+    /// );
+    /// ```
+    pub fn cleanup_report(&mut self) -> CleanupStats {
+        let removed_duplicates = match self.clean.get() {
+            Some(_) => 0,
+            None => {
+                let dirty = self.dirty.get_mut();
+                let mut entries = mem::take(dirty);
+                let removed = Self::sort_and_dedup_entries(&mut entries);
+                self.clean = OnceLock::from(entries);
+
+                removed
+            }
+        };
+
+        self.cleanup();
+
+        CleanupStats {
+            removed_duplicates,
+            remaining: self.len(),
+        }
+    }
+
+    /// If necessary, prepare internal `Vec` for searching by performing a sort and dedup, keeping
+    /// its capacity.
+    ///
+    /// Prefer this over `Self::cleanup()` if more entries will be added to the map later.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new_with_capacity(16);
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    /// map.sort_and_dedup();
+    ///
+    /// assert_eq!(map.try_search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &"a");
+    /// ```
+    pub fn sort_and_dedup(&mut self) {
+        self.entries();
+    }
+
+    /// Reserve capacity in the internal `Vec` for at least `additional` more entries.
+    ///
+    /// Useful before inserting a batch of entries of a known size.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map: IpAddrMap<Ipv4Addr, &str> = IpAddrMap::new();
+    /// map.reserve(16);
+    /// assert!(map.capacity() >= 16);
+    ///
+    /// map.shrink_to(4);
+    /// assert!(map.capacity() >= 4);
+    /// assert!(map.capacity() < 16);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries_mut().reserve(additional);
+    }
+
+    /// Shrink the capacity of the internal `Vec` to at least `min_capacity`, or the number of
+    /// entries, whichever is greater.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.entries_mut().shrink_to(min_capacity);
+    }
+
+    /// Return the capacity of the internal `Vec`.
+    pub fn capacity(&self) -> usize {
+        match self.clean.get() {
+            Some(entries) => entries.capacity(),
+            None => self.dirty.lock().capacity(),
+        }
+    }
+
+    /// Consume the map and transform the value of each entry, preserving the IP address ranges.
+    ///
+    /// Cleans the map first, if necessary, so the returned map is already clean.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{IpAddrEntry, IpAddrMap};
+    ///
+    /// let entry = IpAddrEntry::new(
+    ///     Ipv4Addr::new(1, 1, 1, 1),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     "a",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert(entry);
+    ///
+    /// let map = map.map_values(|value| value.to_uppercase());
+    /// assert_eq!(map.try_search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), "A");
+    /// ```
+    pub fn map_values<U: PartialEq, F: FnMut(T) -> U>(self, mut f: F) -> IpAddrMap<A, U> {
+        let map_entry = |IpAddrEntry { start, end, value }| IpAddrEntry {
+            start,
+            end,
+            value: f(value),
+        };
+
+        IpAddrMap::from_clean(self.into_entries().into_iter().map(map_entry).collect())
+    }
+
+    /// Compare this map (the old map) against `other` (the new map), returning the entries whose IP
+    /// address ranges were added or removed, and the entries whose values changed.
+    ///
+    /// Cleans both maps first, if necessary.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{IpAddrEntry, IpAddrMap};
+    ///
+    /// let entry = |a: u8, b: u8, value: &'static str| {
+    ///     IpAddrEntry::new(Ipv4Addr::new(a, 0, 0, 0), Ipv4Addr::new(b, 0, 0, 0), value).unwrap()
+    /// };
+    ///
+    /// let mut old = IpAddrMap::new();
+    /// old.insert(entry(1, 2, "a"));
+    /// old.insert(entry(3, 4, "b"));
+    ///
+    /// let mut new = IpAddrMap::new();
+    /// new.insert(entry(3, 4, "c"));
+    /// new.insert(entry(5, 6, "d"));
+    ///
+    /// let diff = old.diff(&new);
+    ///
+    /// assert_eq!(diff.removed, [&entry(1, 2, "a")]);
+    /// assert_eq!(diff.added, [&entry(5, 6, "d")]);
+    /// assert_eq!(diff.changed, [(&entry(3, 4, "b"), &entry(3, 4, "c"))]);
+    /// ```
+    pub fn diff<'m>(&'m self, other: &'m Self) -> MapDiff<'m, A, T> {
+        let (old_entries, new_entries) = (self.entries(), other.entries());
+
+        let mut diff = MapDiff {
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+        };
+
+        // Walk both sorted `Vec`s at once, always advancing the one with the lesser range
+        let (mut old_index, mut new_index) = (0, 0);
+        while let (Some(old), Some(new)) = (old_entries.get(old_index), new_entries.get(new_index))
+        {
+            match (old.start, old.end).cmp(&(new.start, new.end)) {
+                Ordering::Less => {
+                    diff.removed.push(old);
+                    old_index += 1;
+                }
+                Ordering::Greater => {
+                    diff.added.push(new);
+                    new_index += 1;
+                }
+                Ordering::Equal => {
+                    if old.value != new.value {
+                        diff.changed.push((old, new));
+                    }
+
+                    old_index += 1;
+                    new_index += 1;
+                }
+            }
+        }
+
+        diff.removed.extend(&old_entries[old_index..]);
+        diff.added.extend(&new_entries[new_index..]);
+
+        diff
+    }
+
+    /// Return the entry at a given index in the internal `Vec` as a reference.
+    ///
+    /// Cleans the map first, if necessary, so indices follow the order of the IP address ranges.
+    pub fn get_from_index_as_ref(&self, index: usize) -> Result<&IpAddrEntry<A, T>, Error> {
+        self.entries().get(index).ok_or(Error::NoValueFound)
+    }
+
+    /// Return the entry at a given index in the internal `Vec` as a mutable reference.
+    ///
+    /// Cleans the map first, if necessary, so indices follow the order of the IP address ranges.
+    ///
+    /// Because changing the start or end of the entry could break the order of the ranges, the map
+    /// is marked as needing to be cleaned again, which the next search will do. Changing only the
+    /// value can't break the order, but still costs that extra cleaning.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::from_rows([
+    ///     (Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a"),
+    ///     (Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b"),
+    /// ])
+    /// .unwrap();
+    ///
+    /// *map.get_from_index_mut(0).unwrap().value_mut() = "c";
+    /// assert_eq!(map.try_search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &"c");
+    ///
+    /// // Moving the first range after the second one reorders them
+    /// let entry = map.get_from_index_mut(0).unwrap();
+    /// *entry.start_mut() = Ipv4Addr::new(7, 7, 7, 7);
+    /// *entry.end_mut() = Ipv4Addr::new(9, 9, 9, 9);
+    /// assert_eq!(map.get_from_index_as_ref(1).unwrap().value(), &"c");
+    ///
+    /// assert!(map.get_from_index_mut(2).is_err());
+    /// ```
+    pub fn get_from_index_mut(&mut self, index: usize) -> Result<&mut IpAddrEntry<A, T>, Error> {
+        if index >= self.len() {
+            return Err(Error::NoValueFound);
+        }
+
+        // Move the clean entries back, so that they are cleaned again after being modified
+        let entries = self.clean.take().unwrap_or_default();
+        let dirty = self.dirty.get_mut();
+        *dirty = entries;
+
+        Ok(&mut dirty[index])
+    }
+
+    /// Return the number of deduplicated entries, cleaning the map first if necessary.
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    /// Returns true if the map holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    /// Return an iterator over references to the entries in the internal `Vec`, in order.
+    ///
+    /// Cleans the map first, if necessary.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{IpAddrEntry, IpAddrMap};
+    ///
+    /// let entry_a = IpAddrEntry::new(
+    ///     Ipv4Addr::new(1, 1, 1, 1),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     "a",
+    /// )
+    /// .unwrap();
+    ///
+    /// let entry_b = IpAddrEntry::new(
+    ///     Ipv4Addr::new(4, 4, 4, 4),
+    ///     Ipv4Addr::new(6, 6, 6, 6),
+    ///     "b",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert(entry_b);
+    /// map.insert(entry_a);
+    ///
+    /// let values: Vec<_> = map.iter().map(|entry| *entry.value()).collect();
+    /// assert_eq!(values, ["a", "b"]);
+    /// ```
+    pub fn iter(&self) -> core::slice::Iter<'_, IpAddrEntry<A, T>> {
+        self.entries().iter()
+    }
+}
+
+impl<A: IpAddrBits, T: PartialEq> IpAddrMap<A, T> {
+    /// Return the ranges of IP addresses from `universe_start` to `universe_end` (inclusive) that
+    /// are not in any entry of the map, in order, as `(start, end)` tuples.
+    ///
+    /// Returns nothing if `universe_start > universe_end`. Cleans the map first, if necessary.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let map = IpAddrMap::from_rows([
+    ///     (Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 255, 255, 255), "a"),
+    ///     (Ipv4Addr::new(3, 0, 0, 0), Ipv4Addr::new(3, 255, 255, 255), "b"),
+    /// ])
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     map.complement(Ipv4Addr::UNSPECIFIED, Ipv4Addr::BROADCAST),
+    ///     [
+    ///         (Ipv4Addr::new(0, 0, 0, 0), Ipv4Addr::new(0, 255, 255, 255)),
+    ///         (Ipv4Addr::new(2, 0, 0, 0), Ipv4Addr::new(2, 255, 255, 255)),
+    ///         (Ipv4Addr::new(4, 0, 0, 0), Ipv4Addr::BROADCAST),
+    ///     ]
+    /// );
+    ///
+    /// // Only the uncovered parts within the universe are returned
+    /// assert_eq!(
+    ///     map.complement(Ipv4Addr::new(1, 128, 0, 0), Ipv4Addr::new(3, 0, 0, 0)),
+    ///     [(Ipv4Addr::new(2, 0, 0, 0), Ipv4Addr::new(2, 255, 255, 255))]
+    /// );
+    /// assert!(map
+    ///     .complement(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 0, 0, 255))
+    ///     .is_empty());
+    /// ```
+    pub fn complement(&self, universe_start: A, universe_end: A) -> Vec<(A, A)> {
+        let mut gaps = Vec::new();
+
+        if universe_start > universe_end {
+            return gaps;
+        }
+
+        // The first address not yet known to be covered, or `None` once the rest of the universe is
+        let mut uncovered = Some(universe_start);
+
+        for entry in self.entries() {
+            let Some(first_uncovered) = uncovered else {
+                break;
+            };
+
+            if entry.start > universe_end {
+                break;
+            }
+
+            if entry.end < first_uncovered {
+                continue;
+            }
+
+            if entry.start > first_uncovered {
+                let gap_end = entry
+                    .start
+                    .prev_addr()
+                    .expect("an address before one greater than another");
+                gaps.push((first_uncovered, gap_end));
+            }
+
+            uncovered = match entry.end < universe_end {
+                true => entry.end.next_addr(),
+                false => None,
+            };
+        }
+
+        if let Some(first_uncovered) = uncovered {
+            gaps.push((first_uncovered, universe_end));
+        }
+
+        gaps
+    }
+
+    /// Group the entries by `key` of their values, returning the number of entries and the total
+    /// number of addresses in each group: `(entries, addresses)`.
+    ///
+    /// Addresses in overlapping ranges are counted once for each range. Totals saturate at
+    /// `u128::MAX`.
+    ///
+    /// Cleans the map first, if necessary, without requiring mutability.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let map = IpAddrMap::from_rows([
+    ///     (Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 0, 0, 255), "BE"),
+    ///     (Ipv4Addr::new(2, 0, 0, 0), Ipv4Addr::new(2, 0, 0, 15), "BE"),
+    ///     (Ipv4Addr::new(3, 0, 0, 0), Ipv4Addr::new(3, 0, 0, 0), "CA"),
+    /// ])
+    /// .unwrap();
+    ///
+    /// let counts = map.count_by(|code| *code);
+    ///
+    /// assert_eq!(counts["BE"], (2, 272));
+    /// assert_eq!(counts["CA"], (1, 1));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn count_by<K: Eq + Hash, F: Fn(&T) -> K>(&self, key: F) -> HashMap<K, (usize, u128)> {
+        let mut counts: HashMap<K, (usize, u128)> = HashMap::new();
+
+        for entry in self.entries() {
+            let (entries, addresses) = counts.entry(key(&entry.value)).or_default();
+            *entries += 1;
+            *addresses = addresses.saturating_add(entry.count());
+        }
+
+        counts
+    }
+
+    /// Like `Self::cleanup()`, but resolve overlapping ranges according to `policy`.
+    ///
+    /// With `OverlapPolicy::Allow`, this is the same as `Self::cleanup()`. With
+    /// `OverlapPolicy::Error`, the map is cleaned, but `Error::OverlappingRanges` is returned if
+    /// any two ranges overlap. Exact duplicates are removed first, so they don't count as
+    /// overlapping.
+    ///
+    /// Otherwise, each address is resolved to a single entry chosen by `policy`, and the other
+    /// entries containing it are trimmed around it, splitting them into several entries if
+    /// necessary. Entries that are entirely covered by others are removed.
+    ///
+    /// "First" and "last" refer to the order entries were inserted in. Entries that were already
+    /// cleaned count as inserted in sorted order, before any entries inserted since.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::{Error, IpAddrMap, OverlapPolicy};
+    ///
+    /// let layered = || {
+    ///     IpAddrMap::from_rows([
+    ///         (Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 255, 255, 255), "country"),
+    ///         (Ipv4Addr::new(1, 2, 0, 0), Ipv4Addr::new(1, 2, 255, 255), "city"),
+    ///     ])
+    ///     .unwrap()
+    /// };
+    ///
+    /// let mut map = layered();
+    /// map.cleanup_with(OverlapPolicy::KeepMoreSpecific).unwrap();
+    ///
+    /// assert_eq!(map.try_search(Ipv4Addr::new(1, 1, 0, 0)).unwrap(), &"country");
+    /// assert_eq!(map.try_search(Ipv4Addr::new(1, 2, 3, 4)).unwrap(), &"city");
+    /// assert_eq!(map.try_search(Ipv4Addr::new(1, 3, 0, 0)).unwrap(), &"country");
+    /// // The country range is split around the city range
+    /// assert_eq!(map.len(), 3);
+    ///
+    /// let mut map = layered();
+    /// map.cleanup_with(OverlapPolicy::KeepFirst).unwrap();
+    ///
+    /// assert_eq!(map.try_search(Ipv4Addr::new(1, 2, 3, 4)).unwrap(), &"country");
+    /// assert_eq!(map.len(), 1);
+    ///
+    /// let mut map = layered();
+    ///
+    /// assert!(matches!(
+    ///     map.cleanup_with(OverlapPolicy::Error),
+    ///     Err(Error::OverlappingRanges)
+    /// ));
+    /// ```
+    pub fn cleanup_with(&mut self, policy: OverlapPolicy) -> Result<(), Error>
+    where
+        T: Clone,
+    {
+        let mut entries = mem::take(self.entries_mut());
+
+        let result = match policy {
+            OverlapPolicy::Allow => Ok(()),
+            OverlapPolicy::Error => {
+                Self::sort_and_dedup_entries(&mut entries);
+
+                match entries.windows(2).any(|pair| pair[0].end >= pair[1].start) {
+                    true => Err(Error::OverlappingRanges),
+                    false => Ok(()),
+                }
+            }
+            OverlapPolicy::KeepFirst => {
+                entries = Self::trim_overlaps(entries);
+
+                Ok(())
+            }
+            OverlapPolicy::KeepLast => {
+                entries.reverse();
+                entries = Self::trim_overlaps(entries);
+
+                Ok(())
+            }
+            OverlapPolicy::KeepMoreSpecific => {
+                // Stable, so ties keep the first inserted
+                entries.sort_by_key(IpAddrEntry::count);
+                entries = Self::trim_overlaps(entries);
+
+                Ok(())
+            }
+        };
+
+        Self::sort_and_dedup_entries(&mut entries);
+        self.clean = OnceLock::from(entries);
+        self.cleanup();
+
+        result
+    }
+
+    /// Keep the parts of each of `entries` that are not covered by an entry before it, splitting an
+    /// entry into several if an earlier entry covers its middle.
+    ///
+    /// The returned entries do not overlap, and are not sorted.
+    fn trim_overlaps(entries: Vec<IpAddrEntry<A, T>>) -> Vec<IpAddrEntry<A, T>>
+    where
+        T: Clone,
+    {
+        // The ranges already kept, as a map of `start` to `end`, which never overlap
+        let mut covered: BTreeMap<A, A> = BTreeMap::new();
+        let mut trimmed = Vec::with_capacity(entries.len());
+
+        for IpAddrEntry { start, end, value } in entries {
+            // A range starting before `start` may still cover the beginning of this one
+            let before = covered.range(..start).next_back();
+            let overlapping: Vec<(A, A)> = before
+                .into_iter()
+                .chain(covered.range(start..=end))
+                .map(|(&start, &end)| (start, end))
+                .collect();
+
+            // The first address not yet known to be covered, or `None` once the rest of it is
+            let mut uncovered = Some(start);
+
+            for (covered_start, covered_end) in overlapping {
+                let Some(first_uncovered) = uncovered else {
+                    break;
+                };
+
+                if covered_end < first_uncovered {
+                    continue;
+                }
+
+                if covered_start > first_uncovered {
+                    let gap_end = covered_start
+                        .prev_addr()
+                        .expect("an address before one greater than another");
+                    covered.insert(first_uncovered, gap_end);
+                    trimmed.push(IpAddrEntry {
+                        start: first_uncovered,
+                        end: gap_end,
+                        value: value.clone(),
+                    });
+                }
+
+                uncovered = match covered_end < end {
+                    true => covered_end.next_addr(),
+                    false => None,
+                };
+            }
+
+            if let Some(first_uncovered) = uncovered {
+                covered.insert(first_uncovered, end);
+                trimmed.push(IpAddrEntry {
+                    start: first_uncovered,
+                    end,
+                    value,
+                });
+            }
+        }
+
+        trimmed
+    }
+}
+
+impl<A: Ord + Copy, T: PartialEq + Clone> Clone for IpAddrMap<A, T> {
+    /// Clone the map, including any entries not yet cleaned.
+    ///
+    /// Cleans the map first, if necessary, so the clone is always clean.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrMap;
+    ///
+    /// let mut map = IpAddrMap::new();
+    /// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+    ///     .unwrap();
+    ///
+    /// let mut clone = map.clone();
+    /// clone
+    ///     .insert_range(Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(clone.len(), 2);
+    /// ```
+    fn clone(&self) -> Self {
+        Self::from_clean(self.entries().to_vec())
+    }
+}
+
+impl<A: Ord + Copy, T: PartialEq> Default for IpAddrMap<A, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Ord + Copy, T: PartialEq> IntoIterator for IpAddrMap<A, T> {
+    type Item = IpAddrEntry<A, T>;
+
+    type IntoIter = alloc::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_entries().into_iter()
+    }
+}
+
+impl<'a, A: Ord + Copy, T: PartialEq> IntoIterator for &'a IpAddrMap<A, T> {
+    type Item = &'a IpAddrEntry<A, T>;
+
+    type IntoIter = core::slice::Iter<'a, IpAddrEntry<A, T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Incrementally builds a clean `IpAddrMap`, collapsing duplicate IP address ranges as they are
+/// inserted.
+///
+/// Entries are kept sorted by IP address range, so `.build()` does not need to sort them. Compared
+/// to `IpAddrMap::insert()` and `IpAddrMap::cleanup()`, inserting is slower, but peak memory usage
+/// is lower for inputs with many duplicate ranges.
+///
+/// If multiple entries share an IP address range, the first one inserted is kept.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::Ipv4Addr;
+/// use ip_geo::IpAddrMapBuilder;
+///
+/// let mut builder = IpAddrMapBuilder::new();
+///
+/// builder
+///     .insert_range(Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "b")
+///     .unwrap();
+/// builder
+///     .insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+///     .unwrap();
+/// builder
+///     .insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+///     .unwrap();
+/// assert_eq!(builder.len(), 2);
+///
+/// let map = builder.build();
+///
+/// assert_eq!(map.try_search(Ipv4Addr::new(2, 2, 2, 2)).unwrap(), &"a");
+/// assert_eq!(map.try_search(Ipv4Addr::new(5, 5, 5, 5)).unwrap(), &"b");
+/// ```
+#[derive(Debug)]
+pub struct IpAddrMapBuilder<A: Ord + Copy, T: PartialEq> {
+    inner: BTreeMap<(A, A), T>,
+}
+
+impl<A: Ord + Copy, T: PartialEq> IpAddrMapBuilder<A, T> {
+    /// Create a new, empty instance of `Self`.
+    pub const fn new() -> Self {
+        Self {
+            inner: BTreeMap::new(),
+        }
+    }
+
+    /// Add another entry into the builder, unless an entry with the same IP address range was
+    /// already added.
+    pub fn insert(&mut self, entry: IpAddrEntry<A, T>) {
+        let IpAddrEntry { start, end, value } = entry;
+
+        self.inner.entry((start, end)).or_insert(value);
+    }
+
+    /// Add another entry into the builder, constructed from a range of IP addresses and a value.
+    ///
+    /// Returns `Error::EmptyRangeError` if `start > end`, leaving the builder unchanged.
+    pub fn insert_range(&mut self, start: A, end: A, value: T) -> Result<(), Error> {
+        self.insert(IpAddrEntry::new(start, end, value)?);
+
+        Ok(())
+    }
+
+    /// Return the number of distinct IP address ranges added so far.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if no entries have been added.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Consume the builder and return a clean `IpAddrMap` holding its entries.
+    pub fn build(self) -> IpAddrMap<A, T> {
+        let entries = self
+            .inner
+            .into_iter()
+            .map(|((start, end), value)| IpAddrEntry { start, end, value })
+            .collect();
+
+        IpAddrMap::from_clean(entries)
+    }
+}
+
+impl<A: Ord + Copy, T: PartialEq> Default for IpAddrMapBuilder<A, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A cleaned `IpAddrMap` with a secondary index from a key derived from each value (ex. a country
+/// code) to the entries holding it, created with `IpAddrMap::with_index`.
+///
+/// Finding every entry for a key takes a lookup in the index rather than a scan of the whole map,
+/// at the cost of the memory for the index. The map can't be modified, so that the index can't
+/// become stale, but it dereferences to an `IpAddrMap` for searching.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::Ipv4Addr;
+/// use ip_geo::IpAddrMap;
+///
+/// let mut map = IpAddrMap::new();
+/// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "BE Belgium")
+///     .unwrap();
+/// map.insert_range(Ipv4Addr::new(4, 4, 4, 4), Ipv4Addr::new(6, 6, 6, 6), "CA Canada")
+///     .unwrap();
+/// map.insert_range(Ipv4Addr::new(7, 7, 7, 7), Ipv4Addr::new(9, 9, 9, 9), "BE Belgium")
+///     .unwrap();
+///
+/// let map = map.with_index(|value| value.split_once(' ').unwrap().0.to_owned());
+///
+/// let belgium: Vec<_> = map.entries_for_key("BE").map(|e| *e.start()).collect();
+/// assert_eq!(belgium, [Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(7, 7, 7, 7)]);
+/// assert_eq!(map.entries_for_key("NL").count(), 0);
+///
+/// // Still searchable as an `IpAddrMap`
+/// assert_eq!(map.try_search(Ipv4Addr::new(5, 5, 5, 5)).unwrap(), &"CA Canada");
+/// ```
+#[derive(Clone, Debug)]
+pub struct IndexedIpAddrMap<A: Ord + Copy, T: PartialEq, K: Ord> {
+    map: IpAddrMap<A, T>,
+    /// The indices of the entries of `map` holding values with each key, in ascending order.
+    index: BTreeMap<K, Vec<usize>>,
+}
+
+impl<A: Ord + Copy, T: PartialEq> IpAddrMap<A, T> {
+    /// Clean the map and build a secondary index from the key `key_fn` derives from each value to
+    /// the entries holding it.
+    ///
+    /// See `IndexedIpAddrMap` for an example.
+    pub fn with_index<K: Ord>(mut self, key_fn: impl Fn(&T) -> K) -> IndexedIpAddrMap<A, T, K> {
+        self.cleanup();
+
+        let mut index: BTreeMap<K, Vec<usize>> = BTreeMap::new();
+        for (entry_index, entry) in self.iter().enumerate() {
+            index
+                .entry(key_fn(entry.value()))
+                .or_default()
+                .push(entry_index);
         }
+
+        IndexedIpAddrMap { map: self, index }
+    }
+}
+
+impl<A: Ord + Copy, T: PartialEq, K: Ord> IndexedIpAddrMap<A, T, K> {
+    /// Return every entry whose value has the given key, in ascending order.
+    pub fn entries_for_key<Q: Ord + ?Sized>(
+        &self,
+        key: &Q,
+    ) -> impl Iterator<Item = &IpAddrEntry<A, T>> + '_
+    where
+        K: Borrow<Q>,
+    {
+        let entries = self.map.entries();
+
+        self.index
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(move |&index| &entries[index])
+    }
+
+    /// Discard the index, returning the underlying map.
+    pub fn into_inner(self) -> IpAddrMap<A, T> {
+        self.map
     }
+}
+
+impl<A: Ord + Copy, T: PartialEq, K: Ord> Deref for IndexedIpAddrMap<A, T, K> {
+    type Target = IpAddrMap<A, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+/// An immutable map of possibly overlapping IP address ranges, stored as an interval tree so that
+/// every range containing an address can be found without scanning.
+///
+/// `IpAddrMap` assumes that its ranges do not overlap: `try_search()` returns an arbitrary match
+/// if they do, and `search_all()` scans every entry that starts before the address. For layered
+/// databases with intentionally overlapping ranges (ex. country ranges containing more specific
+/// city ranges), an `IntervalMap` returns every containing range in `O(log n + k)` time, for `k`
+/// matches, and `search()` returns the most specific one. For maps without overlapping ranges,
+/// `IpAddrMap::try_search()` is faster (see `benches/search.rs`).
+///
+/// The entries are sorted as in `IpAddrMap`, and each one is the root of an implicit, balanced
+/// subtree of the entries around it, annotated with the greatest end of any range in it.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::Ipv4Addr;
+/// use ip_geo::IpAddrMap;
+///
+/// let mut map = IpAddrMap::new();
+/// map.insert_range(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 255, 255, 255), "country")
+///     .unwrap();
+/// map.insert_range(Ipv4Addr::new(1, 2, 0, 0), Ipv4Addr::new(1, 2, 255, 255), "city")
+///     .unwrap();
+///
+/// let map = map.into_interval_map();
+///
+/// assert_eq!(map.search(Ipv4Addr::new(1, 2, 3, 4)).unwrap(), &"city");
+/// assert_eq!(map.search(Ipv4Addr::new(1, 3, 0, 0)).unwrap(), &"country");
+/// assert!(map.search(Ipv4Addr::new(2, 0, 0, 0)).is_err());
+///
+/// let values: Vec<_> = map
+///     .search_all(Ipv4Addr::new(1, 2, 3, 4))
+///     .into_iter()
+///     .map(|entry| *entry.value())
+///     .collect();
+/// assert_eq!(values, ["country", "city"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct IntervalMap<A: Ord + Copy, T: PartialEq> {
+    /// The entries, sorted by IP address range.
+    entries: Vec<IpAddrEntry<A, T>>,
+    /// For the subtree rooted at the entry of the same index, the greatest end of any range in it.
+    max_ends: Vec<A>,
+}
 
-    /// Create a new instance of `Self` with a starting capacity for the internal `Vec`.
-    pub fn new_with_capacity(capacity: usize) -> Self {
-        Self {
-            inner: Vec::with_capacity(capacity),
-            dirty: false,
-        }
+impl<A: Ord + Copy, T: PartialEq> IpAddrMap<A, T> {
+    /// Clean the map and convert it into an `IntervalMap`, for searching overlapping ranges.
+    ///
+    /// See `IntervalMap` for an example.
+    pub fn into_interval_map(self) -> IntervalMap<A, T> {
+        IntervalMap::from(self)
     }
+}
 
-    /// Add another entry into the map.
-    pub fn insert(&mut self, entry: IpAddrEntry<A, T>) {
-        self.inner.push(entry);
-        self.dirty = true;
+impl<A: Ord + Copy, T: PartialEq> IntervalMap<A, T> {
+    /// For a given IP address, find the value of the most specific stored entry that contains it.
+    ///
+    /// Of the containing entries, the most specific is the one that starts last, and of those, the
+    /// one that ends first. Returns `Error::NoValueFound` if no entry contains the address.
+    pub fn search(&self, address: A) -> Result<&T, Error> {
+        self.search_entry(address).map(IpAddrEntry::value)
     }
 
-    /// For a given IP address, find the value of the stored entries the contains it, else `None`.
+    /// For a given IP address, find the most specific stored entry that contains it.
     ///
-    /// Cleans the map first, if necessary.
-    pub fn search(&mut self, address: A) -> Result<&T, Error> {
-        // Cleans the map, making `search_unsafe()` safe to use.
-        self.cleanup();
+    /// See `search()` for which entry is the most specific.
+    pub fn search_entry(&self, address: A) -> Result<&IpAddrEntry<A, T>, Error> {
+        let mut most_specific: Option<&IpAddrEntry<A, T>> = None;
 
-        self.try_search(address)
+        self.visit_containing(0, self.entries.len(), address, &mut |entry| {
+            let is_more_specific = most_specific.map_or(true, |best| {
+                (entry.start, best.end) > (best.start, entry.end)
+            });
+
+            if is_more_specific {
+                most_specific = Some(entry);
+            }
+        });
+
+        most_specific.ok_or(Error::NoValueFound)
     }
 
-    /// For a given IP address, find the value of the stored entries the contains it, else `None`.
+    /// For a given IP address, return every stored entry that contains it, in sorted order.
     ///
-    /// Requires that the map be clean, call `.cleanup()` before using this function, or use
-    /// `.search()` instead if you have mutability.
-    pub fn try_search(&self, address: A) -> Result<&T, Error> {
-        if self.dirty {
-            return Err(Error::DirtyIpAddrMap);
-        }
+    /// Returns an empty `Vec` if no entry contains the address.
+    pub fn search_all(&self, address: A) -> Vec<&IpAddrEntry<A, T>> {
+        let mut containing = Vec::new();
 
-        let index = self
-            .inner
-            .binary_search_by(|e| e.partial_cmp(&address).unwrap())
-            .map_err(|_| Error::NoValueFound)?;
+        self.visit_containing(0, self.entries.len(), address, &mut |entry| {
+            containing.push(entry);
+        });
 
-        // Safety: `binary_search_by` would already have returned an error if the index didn't exist
-        Ok(self.inner[index].value())
+        containing
     }
 
-    /// If necessary, prepare internal `Vec` for searching by performing a dedup, sort, and shrink.
-    ///
-    /// This is called by `Self::search()`, it should not be necessary to perform manually unless
-    /// it is used in an interactive program and you want to do as much work as possible before interactivity.
-    pub fn cleanup(&mut self) {
-        if !self.dirty {
+    /// Call `visit` with every entry containing `address` in the subtree of the entries from `low`
+    /// up to `high`, in sorted order.
+    fn visit_containing<'m>(
+        &'m self,
+        low: usize,
+        high: usize,
+        address: A,
+        visit: &mut impl FnMut(&'m IpAddrEntry<A, T>),
+    ) {
+        if low >= high {
+            return;
+        }
+
+        let root = low + (high - low) / 2;
+
+        // No range in this subtree reaches `address`
+        if self.max_ends[root] < address {
             return;
         }
 
-        self.inner.dedup_by(|a, b| a == b);
-        self.inner.sort_unstable_by_key(|e| (e.start, e.end));
-        self.inner.shrink_to_fit(); // Assumes that you will only ever cleanup after you're done
-                                    // adding to the map.
-        self.dirty = false;
+        self.visit_containing(low, root, address, visit);
+
+        let entry = &self.entries[root];
+
+        // This range and every range after it start after `address`
+        if entry.start > address {
+            return;
+        }
+
+        if entry.end >= address {
+            visit(entry);
+        }
+
+        self.visit_containing(root + 1, high, address, visit);
     }
 
-    /// Return the entry at a given index in the internal `Vec` as a reference.
-    pub fn get_from_index_as_ref(&self, index: usize) -> Result<&IpAddrEntry<A, T>, Error> {
-        self.inner.get(index).ok_or(Error::NoValueFound)
+    /// Fill `max_ends` for the subtree of the entries from `low` up to `high`, returning the
+    /// greatest end in it, if it is not empty.
+    fn build_max_ends(&mut self, low: usize, high: usize) -> Option<A> {
+        if low >= high {
+            return None;
+        }
+
+        let root = low + (high - low) / 2;
+        let max_end = [
+            self.build_max_ends(low, root),
+            self.build_max_ends(root + 1, high),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(self.entries[root].end, A::max);
+
+        self.max_ends[root] = max_end;
+
+        Some(max_end)
     }
 
-    /// Return the length of the internal `Vec`.
+    /// Return the number of stored entries.
     pub fn len(&self) -> usize {
-        self.inner.len()
+        self.entries.len()
     }
 
-    /// Returns true if the internal `Vec` is empty.
+    /// Return true if there are no stored entries.
     pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+        self.entries.is_empty()
+    }
+
+    /// Return an iterator over the stored entries, in sorted order.
+    pub fn iter(&self) -> core::slice::Iter<'_, IpAddrEntry<A, T>> {
+        self.entries.iter()
+    }
+
+    /// Discard the interval tree, returning a clean `IpAddrMap` of the same entries.
+    pub fn into_inner(self) -> IpAddrMap<A, T> {
+        IpAddrMap::from_clean(self.entries)
     }
 }
 
-impl<A: Ord + Copy, T: PartialEq> Default for IpAddrMap<A, T> {
-    fn default() -> Self {
-        Self::new()
+impl<A: Ord + Copy, T: PartialEq> From<IpAddrMap<A, T>> for IntervalMap<A, T> {
+    fn from(value: IpAddrMap<A, T>) -> Self {
+        let entries = value.into_entries();
+        let max_ends = entries.iter().map(|entry| entry.end).collect();
+
+        let mut map = Self { entries, max_ends };
+        map.build_max_ends(0, map.entries.len());
+
+        map
     }
 }
 
-impl<A: Ord + Copy, T: PartialEq> IntoIterator for IpAddrMap<A, T> {
-    type Item = IpAddrEntry<A, T>;
+/// An `IpAddrMap` holding either IPv4 or IPv6 addresses, searchable by any `IpAddr`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+/// use ip_geo::{AnyIpAddrMap, Error, IpAddrMap};
+///
+/// let mut map = IpAddrMap::new();
+/// map.insert_range(Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(3, 3, 3, 3), "a")
+///     .unwrap();
+/// let map = AnyIpAddrMap::from(map);
+///
+/// assert_eq!(map.search(IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2))).unwrap(), &"a");
+/// assert!(matches!(
+///     map.search(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+///     Err(Error::AddressFamilyMismatch(_))
+/// ));
+/// ```
+#[derive(Debug)]
+pub enum AnyIpAddrMap<T: PartialEq> {
+    V4(IpAddrMap<Ipv4Addr, T>),
+    V6(IpAddrMap<Ipv6Addr, T>),
+}
+
+impl<T: PartialEq> AnyIpAddrMap<T> {
+    /// For a given IP address, find the value of the stored entry that contains it.
+    ///
+    /// Returns `Error::AddressFamilyMismatch` if `address` is not in the same address family as
+    /// the map. IPv4-mapped IPv6 addresses are not converted, use `normalize_addr()` for that.
+    pub fn search(&self, address: IpAddr) -> Result<&T, Error> {
+        match (self, address) {
+            (Self::V4(map), IpAddr::V4(address)) => map.try_search(address),
+            (Self::V6(map), IpAddr::V6(address)) => map.try_search(address),
+            _ => Err(Error::AddressFamilyMismatch(address)),
+        }
+    }
+}
 
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+impl<T: PartialEq> From<IpAddrMap<Ipv4Addr, T>> for AnyIpAddrMap<T> {
+    fn from(value: IpAddrMap<Ipv4Addr, T>) -> Self {
+        Self::V4(value)
+    }
+}
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.inner.into_iter()
+impl<T: PartialEq> From<IpAddrMap<Ipv6Addr, T>> for AnyIpAddrMap<T> {
+    fn from(value: IpAddrMap<Ipv6Addr, T>) -> Self {
+        Self::V6(value)
+    }
+}
+
+/// Statistics about a cleanup, as returned by `IpAddrMap::cleanup_report()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CleanupStats {
+    /// The number of exact duplicate entries that were removed.
+    pub removed_duplicates: usize,
+    /// The number of entries remaining in the map.
+    pub remaining: usize,
+}
+
+/// How `IpAddrMap::cleanup_with()` resolves overlapping ranges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Return `Error::OverlappingRanges` if any two ranges overlap.
+    Error,
+    /// Resolve each address to the first inserted entry that contains it.
+    KeepFirst,
+    /// Resolve each address to the last inserted entry that contains it.
+    KeepLast,
+    /// Resolve each address to the smallest entry that contains it, or the first inserted of the
+    /// smallest, ex. a city range rather than the country range containing it.
+    KeepMoreSpecific,
+    /// Keep overlapping ranges as they are, as `IpAddrMap::cleanup()` does.
+    ///
+    /// Searching an address in more than one range finds an arbitrary one of them.
+    #[default]
+    Allow,
+}
+
+/// The kind of network an IP address belongs to, as returned by `classify()`.
+///
+/// Only `Public` IP addresses are routable on the internet, so only they can be associated with a
+/// country.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressClass {
+    /// A private network, ex. `192.168.0.1` (RFC 1918) or `fd00::1` (unique local, RFC 4193).
+    Private,
+    /// The local host, ex. `127.0.0.1` or `::1`.
+    Loopback,
+    /// A link-local address, ex. `169.254.0.1` or `fe80::1`.
+    LinkLocal,
+    /// A multicast group, ex. `224.0.0.1` or `ff02::1`.
+    Multicast,
+    /// Any other special-purpose range, ex. unspecified (`0.0.0.0`), documentation (`192.0.2.0/24`,
+    /// `2001:db8::/32`), carrier-grade NAT (`100.64.0.0/10`), or the future use block
+    /// (`240.0.0.0/4`).
+    Reserved,
+    /// A globally routable address.
+    Public,
+}
+
+impl AddressClass {
+    /// Return the name of the class in lowercase snake case, ex. `"link_local"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::Loopback => "loopback",
+            Self::LinkLocal => "link_local",
+            Self::Multicast => "multicast",
+            Self::Reserved => "reserved",
+            Self::Public => "public",
+        }
     }
 }
 
+impl Display for AddressClass {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An entry of an old `IpAddrMap` and the entry of a new one with the same IP address range, but a
+/// different value: `(old, new)`.
+pub type ChangedPair<'m, A, T> = (&'m IpAddrEntry<A, T>, &'m IpAddrEntry<A, T>);
+
+/// The differences between an old and a new `IpAddrMap`, as returned by `IpAddrMap::diff()`.
+#[derive(Debug, PartialEq)]
+pub struct MapDiff<'m, A: Ord + Copy, T> {
+    /// Entries whose IP address ranges are only in the new map.
+    pub added: Vec<&'m IpAddrEntry<A, T>>,
+    /// Entries whose IP address ranges are only in the old map.
+    pub removed: Vec<&'m IpAddrEntry<A, T>>,
+    /// Entries whose IP address ranges are in both maps, but whose values differ: `(old, new)`
+    pub changed: Vec<ChangedPair<'m, A, T>>,
+}
+
 /// Stores a range of IP addresses and a value.
 ///
 /// Example usage:
@@ -241,6 +1765,198 @@ impl<A: Ord + Copy, T> IpAddrEntry<A, T> {
 
         (start, end, value)
     }
+
+    /// Compare the stored IP address range against an IP address.
+    ///
+    /// Returns `Ordering::Less` if the range is entirely before `address`, `Ordering::Greater` if
+    /// it is entirely after `address`, and `Ordering::Equal` if it contains `address`.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::{cmp::Ordering, net::Ipv4Addr};
+    /// use ip_geo::IpAddrEntry;
+    ///
+    /// let entry = IpAddrEntry::new(
+    ///     Ipv4Addr::new(1, 1, 1, 1),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     "contents",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(entry.cmp_address(&Ipv4Addr::new(0, 0, 0, 0)), Ordering::Greater);
+    /// assert_eq!(entry.cmp_address(&Ipv4Addr::new(2, 2, 2, 2)), Ordering::Equal);
+    /// assert_eq!(entry.cmp_address(&Ipv4Addr::new(4, 4, 4, 4)), Ordering::Less);
+    /// ```
+    pub fn cmp_address(&self, address: &A) -> Ordering {
+        if address < &self.start {
+            Ordering::Greater
+        } else if address > &self.end {
+            Ordering::Less
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    /// Return whether the stored IP address range contains every address of `other`.
+    ///
+    /// An empty `other` (ex. `b..=a` where `a < b`) holds no addresses, so is contained by every
+    /// entry.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrEntry;
+    ///
+    /// let entry = IpAddrEntry::new(
+    ///     Ipv4Addr::new(1, 1, 1, 1),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     "contents",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert!(entry.contains_range(&(Ipv4Addr::new(2, 2, 2, 2)..=Ipv4Addr::new(3, 3, 3, 3))));
+    /// assert!(entry.contains_range(&entry.range()));
+    /// assert!(!entry.contains_range(&(Ipv4Addr::new(2, 2, 2, 2)..=Ipv4Addr::new(4, 4, 4, 4))));
+    /// ```
+    pub fn contains_range(&self, other: &RangeInclusive<A>) -> bool {
+        other.is_empty() || (self.start <= *other.start() && *other.end() <= self.end)
+    }
+
+    /// Return whether the stored IP address range shares at least one address with `other`.
+    ///
+    /// An empty `other` (ex. `b..=a` where `a < b`) holds no addresses, so overlaps no entry.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrEntry;
+    ///
+    /// let entry = IpAddrEntry::new(
+    ///     Ipv4Addr::new(1, 1, 1, 1),
+    ///     Ipv4Addr::new(3, 3, 3, 3),
+    ///     "contents",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert!(entry.overlaps(&(Ipv4Addr::new(3, 3, 3, 3)..=Ipv4Addr::new(4, 4, 4, 4))));
+    /// assert!(entry.overlaps(&(Ipv4Addr::new(0, 0, 0, 0)..=Ipv4Addr::new(9, 9, 9, 9))));
+    /// assert!(!entry.overlaps(&(Ipv4Addr::new(4, 4, 4, 4)..=Ipv4Addr::new(5, 5, 5, 5))));
+    /// ```
+    pub fn overlaps(&self, other: &RangeInclusive<A>) -> bool {
+        !other.is_empty() && self.start <= *other.end() && *other.start() <= self.end
+    }
+}
+
+impl<A: IpAddrBits, T> IpAddrEntry<A, T> {
+    /// Return the number of IP addresses in the stored IP address range.
+    ///
+    /// A range covering the entire IPv6 address space holds one more address than `u128::MAX`, so
+    /// the count saturates at `u128::MAX`.
+    ///
+    /// An inverted range, with `start > end` (ex. after `start_mut()`), holds no addresses.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::{Ipv4Addr, Ipv6Addr};
+    /// use ip_geo::IpAddrEntry;
+    ///
+    /// let entry = IpAddrEntry::new(Ipv4Addr::new(1, 1, 1, 0), Ipv4Addr::new(1, 1, 1, 255), ())
+    ///     .unwrap();
+    /// assert_eq!(entry.count(), 256);
+    ///
+    /// let entry = IpAddrEntry::new(Ipv6Addr::UNSPECIFIED, Ipv6Addr::UNSPECIFIED, ()).unwrap();
+    /// assert_eq!(entry.count(), 1);
+    ///
+    /// let entry = IpAddrEntry::new(Ipv6Addr::UNSPECIFIED, Ipv6Addr::from_bits(u128::MAX), ())
+    ///     .unwrap();
+    /// assert_eq!(entry.count(), u128::MAX);
+    ///
+    /// let mut entry = IpAddrEntry::new(Ipv4Addr::new(1, 1, 1, 0), Ipv4Addr::new(1, 1, 1, 255), ())
+    ///     .unwrap();
+    /// *entry.start_mut() = Ipv4Addr::new(2, 0, 0, 0);
+    /// assert_eq!(entry.count(), 0);
+    /// ```
+    pub fn count(&self) -> u128 {
+        // `start_mut()` and `end_mut()` can invert a range after `Self::new()` checked it
+        self.end
+            .to_u128()
+            .checked_sub(self.start.to_u128())
+            .map_or(0, |difference| difference.saturating_add(1))
+    }
+
+    /// Return the stored IP address range as the smallest list of CIDR blocks that exactly covers
+    /// it, as tuples of the network address and the prefix length: `(network, prefix)`
+    ///
+    /// An inverted range, with `start > end` (ex. after `start_mut()`), returns no blocks.
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::Ipv4Addr;
+    /// use ip_geo::IpAddrEntry;
+    ///
+    /// let entry = IpAddrEntry::new(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 255, 255, 255), ())
+    ///     .unwrap();
+    /// assert_eq!(entry.to_cidrs(), vec![(Ipv4Addr::new(1, 0, 0, 0), 8)]);
+    ///
+    /// let entry = IpAddrEntry::new(Ipv4Addr::new(1, 0, 0, 1), Ipv4Addr::new(1, 0, 0, 6), ())
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     entry.to_cidrs(),
+    ///     vec![
+    ///         (Ipv4Addr::new(1, 0, 0, 1), 32),
+    ///         (Ipv4Addr::new(1, 0, 0, 2), 31),
+    ///         (Ipv4Addr::new(1, 0, 0, 4), 31),
+    ///         (Ipv4Addr::new(1, 0, 0, 6), 32),
+    ///     ]
+    /// );
+    ///
+    /// let entry = IpAddrEntry::new(Ipv4Addr::UNSPECIFIED, Ipv4Addr::BROADCAST, ()).unwrap();
+    /// assert_eq!(entry.to_cidrs(), vec![(Ipv4Addr::UNSPECIFIED, 0)]);
+    ///
+    /// let mut entry = IpAddrEntry::new(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 0, 0, 255), ())
+    ///     .unwrap();
+    /// *entry.end_mut() = Ipv4Addr::new(0, 255, 255, 255);
+    /// assert_eq!(entry.to_cidrs(), vec![]);
+    /// ```
+    pub fn to_cidrs(&self) -> Vec<(A, u8)> {
+        // For a block of `2^size` addresses, the mask of its host bits
+        let host_mask = |size: u32| match size {
+            u128::BITS => u128::MAX,
+            _ => (1 << size) - 1,
+        };
+
+        let end = self.end.to_u128();
+        let mut start = self.start.to_u128();
+        let mut cidrs = vec![];
+
+        // `start_mut()` and `end_mut()` can invert a range after `Self::new()` checked it, and no
+        // block fits in an inverted range
+        if start > end {
+            return cidrs;
+        }
+
+        loop {
+            // The largest block that `start` is aligned to, that does not extend beyond `end`
+            let mut size = start.trailing_zeros().min(A::BITS);
+            while start | host_mask(size) > end {
+                size -= 1;
+            }
+
+            cidrs.push((A::from_u128(start), (A::BITS - size) as u8));
+
+            let last = start | host_mask(size);
+            if last == end {
+                return cidrs;
+            }
+
+            start = last + 1;
+        }
+    }
 }
 
 impl<A: Ord + Copy, T> PartialEq<A> for IpAddrEntry<A, T> {
@@ -250,22 +1966,324 @@ impl<A: Ord + Copy, T> PartialEq<A> for IpAddrEntry<A, T> {
 }
 
 impl<A: Ord + Copy, T> PartialOrd<A> for IpAddrEntry<A, T> {
-    fn partial_cmp(&self, other: &A) -> Option<std::cmp::Ordering> {
-        match other {
-            v if v > &self.end => Some(Ordering::Less),
-            v if v < &self.start => Some(Ordering::Greater),
-            v if self == v => Some(Ordering::Equal),
-            _ => unreachable!(),
+    fn partial_cmp(&self, other: &A) -> Option<Ordering> {
+        Some(self.cmp_address(other))
+    }
+}
+
+/// For a given IP address, convert IPv4-mapped (ex. `::ffff:1.2.3.4`) and IPv4-compatible (ex.
+/// `::1.2.3.4`) IPv6 addresses into the IPv4 addresses they represent.
+///
+/// This allows addresses presented by dual-stack clients to be resolved against an IPv4 database.
+/// The unspecified (`::`) and loopback (`::1`) IPv6 addresses are not considered IPv4-compatible.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{
+///     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+///     str::FromStr,
+/// };
+/// use ip_geo::{normalize_addr, IpAddrEntry, IpAddrMap};
+///
+/// let mapped = IpAddr::from_str("::ffff:1.2.3.4").unwrap();
+/// let compatible = IpAddr::from_str("::1.2.3.4").unwrap();
+/// let ipv4 = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+///
+/// assert_eq!(normalize_addr(mapped), ipv4);
+/// assert_eq!(normalize_addr(compatible), ipv4);
+/// assert_eq!(normalize_addr(ipv4), ipv4);
+/// assert_eq!(normalize_addr(Ipv6Addr::LOCALHOST.into()), Ipv6Addr::LOCALHOST);
+///
+/// let entry = IpAddrEntry::new(
+///     Ipv4Addr::new(1, 0, 0, 0),
+///     Ipv4Addr::new(1, 255, 255, 255),
+///     "BE",
+/// )
+/// .unwrap();
+///
+/// let mut map = IpAddrMap::new();
+/// map.insert(entry);
+///
+/// let IpAddr::V4(mapped) = normalize_addr(mapped) else {
+///     panic!("expected an IPv4 address");
+/// };
+/// assert_eq!(map.search(mapped).unwrap(), &"BE");
+/// ```
+pub fn normalize_addr(address: IpAddr) -> IpAddr {
+    let IpAddr::V6(ipv6_addr) = address else {
+        return address;
+    };
+
+    if let Some(ipv4_addr) = ipv6_addr.to_ipv4_mapped() {
+        return IpAddr::V4(ipv4_addr);
+    }
+
+    if ipv6_addr.is_unspecified() || ipv6_addr.is_loopback() {
+        return address;
+    }
+
+    match ipv6_addr.to_ipv4() {
+        Some(ipv4_addr) => IpAddr::V4(ipv4_addr),
+        None => address,
+    }
+}
+
+/// For a given IP address of either family, find the value of the entry that contains it in the
+/// map of the same family.
+///
+/// IPv4-mapped and IPv4-compatible IPv6 addresses (ex. `::ffff:1.2.3.4`) are searched in `v4`, as
+/// with `normalize_addr()`. Like `IpAddrMap::try_search()`, the maps are not cleaned first.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{
+///     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+///     str::FromStr,
+/// };
+/// use ip_geo::{search, Error, IpAddrMap};
+///
+/// let mut v4 = IpAddrMap::new();
+/// v4.insert_range(Ipv4Addr::new(1, 0, 0, 0), Ipv4Addr::new(1, 255, 255, 255), "BE")
+///     .unwrap();
+/// v4.cleanup();
+///
+/// let mut v6 = IpAddrMap::new();
+/// v6.insert_range(
+///     Ipv6Addr::from_str("2001:db8::").unwrap(),
+///     Ipv6Addr::from_str("2001:db8::ffff").unwrap(),
+///     "NL",
+/// )
+/// .unwrap();
+/// v6.cleanup();
+///
+/// let ipv4 = IpAddr::from_str("1.2.3.4").unwrap();
+/// let mapped = IpAddr::from_str("::ffff:1.2.3.4").unwrap();
+/// let ipv6 = IpAddr::from_str("2001:db8::1").unwrap();
+///
+/// assert_eq!(search(&v4, &v6, ipv4).unwrap(), &"BE");
+/// assert_eq!(search(&v4, &v6, mapped).unwrap(), &"BE");
+/// assert_eq!(search(&v4, &v6, ipv6).unwrap(), &"NL");
+/// assert!(matches!(
+///     search(&v4, &v6, IpAddr::from_str("5.5.5.5").unwrap()),
+///     Err(Error::NoValueFound)
+/// ));
+/// ```
+pub fn search<'m, T: PartialEq>(
+    v4: &'m IpAddrMap<Ipv4Addr, T>,
+    v6: &'m IpAddrMap<Ipv6Addr, T>,
+    address: IpAddr,
+) -> Result<&'m T, Error> {
+    match normalize_addr(address) {
+        IpAddr::V4(ipv4_addr) => v4.try_search(ipv4_addr),
+        IpAddr::V6(ipv6_addr) => v6.try_search(ipv6_addr),
+    }
+}
+
+/// For a given IP address, zero its last `v4_bits` bits if it is IPv4, or its last `v6_bits` bits
+/// if it is IPv6, so that it no longer identifies a single host.
+///
+/// Counts beyond the size of the address (ex. 40 bits of an IPv4 address) zero the whole address.
+/// `v4_bits = 8` and `v6_bits = 80` keep an IPv4 `/24` and an IPv6 `/48`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{net::IpAddr, str::FromStr};
+/// use ip_geo::anonymize;
+///
+/// let ipv4 = IpAddr::from_str("1.2.3.4").unwrap();
+/// let ipv6 = IpAddr::from_str("2001:db8:1:2:3:4:5:6").unwrap();
+///
+/// assert_eq!(anonymize(ipv4, 8, 80), IpAddr::from_str("1.2.3.0").unwrap());
+/// assert_eq!(anonymize(ipv6, 8, 80), IpAddr::from_str("2001:db8:1::").unwrap());
+/// assert_eq!(anonymize(ipv4, 0, 0), ipv4);
+/// assert_eq!(anonymize(ipv4, 40, 0), IpAddr::from_str("0.0.0.0").unwrap());
+/// ```
+pub fn anonymize(address: IpAddr, v4_bits: u8, v6_bits: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(ipv4_addr) => {
+            let mask = u32::MAX.checked_shl(v4_bits.into()).unwrap_or(0);
+
+            IpAddr::V4(Ipv4Addr::from_bits(ipv4_addr.to_bits() & mask))
+        }
+        IpAddr::V6(ipv6_addr) => {
+            let mask = u128::MAX.checked_shl(v6_bits.into()).unwrap_or(0);
+
+            IpAddr::V6(Ipv6Addr::from_bits(ipv6_addr.to_bits() & mask))
+        }
+    }
+}
+
+/// For a given IP address, return the kind of network it belongs to.
+///
+/// IPv4-mapped and IPv4-compatible IPv6 addresses (ex. `::ffff:10.0.0.1`) are classified as their
+/// IPv4 address, as with `normalize_addr()`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{net::IpAddr, str::FromStr};
+/// use ip_geo::{classify, AddressClass};
+///
+/// let class = |address: &str| classify(IpAddr::from_str(address).unwrap());
+///
+/// assert_eq!(class("10.1.2.3"), AddressClass::Private);
+/// assert_eq!(class("::ffff:192.168.1.1"), AddressClass::Private);
+/// assert_eq!(class("fd12:3456::1"), AddressClass::Private);
+/// assert_eq!(class("127.0.0.1"), AddressClass::Loopback);
+/// assert_eq!(class("::1"), AddressClass::Loopback);
+/// assert_eq!(class("169.254.1.1"), AddressClass::LinkLocal);
+/// assert_eq!(class("fe80::1"), AddressClass::LinkLocal);
+/// assert_eq!(class("ff02::1"), AddressClass::Multicast);
+/// assert_eq!(class("100.64.0.1"), AddressClass::Reserved);
+/// assert_eq!(class("2001:db8::1"), AddressClass::Reserved);
+/// assert_eq!(class("1.1.1.1"), AddressClass::Public);
+/// assert_eq!(class("2606:4700::1111"), AddressClass::Public);
+/// ```
+pub fn classify(address: IpAddr) -> AddressClass {
+    /// Special-purpose IPv4 networks not covered by another class, as `(network, prefix length)`.
+    const RESERVED_IPV4: [(Ipv4Addr, u32); 9] = [
+        (Ipv4Addr::new(0, 0, 0, 0), 8),
+        (Ipv4Addr::new(100, 64, 0, 0), 10),
+        (Ipv4Addr::new(192, 0, 0, 0), 24),
+        (Ipv4Addr::new(192, 0, 2, 0), 24),
+        (Ipv4Addr::new(192, 88, 99, 0), 24),
+        (Ipv4Addr::new(198, 18, 0, 0), 15),
+        (Ipv4Addr::new(198, 51, 100, 0), 24),
+        (Ipv4Addr::new(203, 0, 113, 0), 24),
+        (Ipv4Addr::new(240, 0, 0, 0), 4),
+    ];
+
+    /// Special-purpose IPv6 networks not covered by another class, as `(network, prefix length)`.
+    const RESERVED_IPV6: [(Ipv6Addr, u32); 4] = [
+        (Ipv6Addr::UNSPECIFIED, 128),
+        (Ipv6Addr::new(0x100, 0, 0, 0, 0, 0, 0, 0), 64),
+        (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32),
+        (Ipv6Addr::new(0x3fff, 0, 0, 0, 0, 0, 0, 0), 20),
+    ];
+
+    /// Returns true if `address` is within the network of the given prefix length.
+    fn in_network<A: IpAddrBits>(address: A, (network, prefix_len): (A, u32)) -> bool {
+        let shift = A::BITS - prefix_len;
+
+        address.to_u128().checked_shr(shift) == network.to_u128().checked_shr(shift)
+    }
+
+    /// Unique local IPv6 addresses, ex. `fd00::1`.
+    const UNIQUE_LOCAL: (Ipv6Addr, u32) = (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7);
+    /// Link-local unicast IPv6 addresses, ex. `fe80::1`.
+    const LINK_LOCAL: (Ipv6Addr, u32) = (Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10);
+
+    match normalize_addr(address) {
+        IpAddr::V4(ipv4) if ipv4.is_private() => AddressClass::Private,
+        IpAddr::V4(ipv4) if ipv4.is_loopback() => AddressClass::Loopback,
+        IpAddr::V4(ipv4) if ipv4.is_link_local() => AddressClass::LinkLocal,
+        IpAddr::V4(ipv4) if ipv4.is_multicast() => AddressClass::Multicast,
+        IpAddr::V4(ipv4) if RESERVED_IPV4.into_iter().any(|net| in_network(ipv4, net)) => {
+            AddressClass::Reserved
+        }
+        IpAddr::V6(ipv6) if in_network(ipv6, UNIQUE_LOCAL) => AddressClass::Private,
+        IpAddr::V6(ipv6) if ipv6.is_loopback() => AddressClass::Loopback,
+        IpAddr::V6(ipv6) if in_network(ipv6, LINK_LOCAL) => AddressClass::LinkLocal,
+        IpAddr::V6(ipv6) if ipv6.is_multicast() => AddressClass::Multicast,
+        IpAddr::V6(ipv6) if RESERVED_IPV6.into_iter().any(|net| in_network(ipv6, net)) => {
+            AddressClass::Reserved
         }
+        _ => AddressClass::Public,
+    }
+}
+
+/// Integer conversions for IP address types, used to perform arithmetic on IP address ranges.
+///
+/// This trait is sealed, and is only implemented for `Ipv4Addr` and `Ipv6Addr`.
+pub trait IpAddrBits: private::Sealed + Ord + Copy {
+    /// The size of the IP address type in bits.
+    const BITS: u32;
+
+    /// Return the IP address as an integer, widened to a `u128`.
+    fn to_u128(self) -> u128;
+
+    /// Create an IP address from an integer.
+    ///
+    /// Bits beyond `Self::BITS` are discarded.
+    fn from_u128(bits: u128) -> Self;
+
+    /// Return the IP address after `self`, or `None` if `self` is the last address of the address
+    /// space (ex. `255.255.255.255`).
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::{Ipv4Addr, Ipv6Addr};
+    /// use ip_geo::IpAddrBits;
+    ///
+    /// assert_eq!(Ipv4Addr::new(1, 1, 1, 255).next_addr(), Some(Ipv4Addr::new(1, 1, 2, 0)));
+    /// assert_eq!(Ipv4Addr::BROADCAST.next_addr(), None);
+    /// assert_eq!(Ipv6Addr::from_bits(u128::MAX).next_addr(), None);
+    /// ```
+    fn next_addr(self) -> Option<Self> {
+        let max = u128::MAX >> (u128::BITS - Self::BITS);
+        let bits = self.to_u128();
+
+        (bits < max).then(|| Self::from_u128(bits + 1))
+    }
+
+    /// Return the IP address before `self`, or `None` if `self` is the first address of the
+    /// address space (ex. `0.0.0.0`).
+    ///
+    /// Example usage:
+    ///
+    /// ```rust
+    /// use std::net::{Ipv4Addr, Ipv6Addr};
+    /// use ip_geo::IpAddrBits;
+    ///
+    /// assert_eq!(Ipv4Addr::new(1, 1, 2, 0).prev_addr(), Some(Ipv4Addr::new(1, 1, 1, 255)));
+    /// assert_eq!(Ipv4Addr::UNSPECIFIED.prev_addr(), None);
+    /// assert_eq!(Ipv6Addr::UNSPECIFIED.prev_addr(), None);
+    /// ```
+    fn prev_addr(self) -> Option<Self> {
+        self.to_u128().checked_sub(1).map(Self::from_u128)
+    }
+}
+
+impl IpAddrBits for Ipv4Addr {
+    const BITS: u32 = u32::BITS;
+
+    fn to_u128(self) -> u128 {
+        self.to_bits().into()
+    }
+
+    fn from_u128(bits: u128) -> Self {
+        Ipv4Addr::from_bits(bits as u32)
+    }
+}
+
+impl IpAddrBits for Ipv6Addr {
+    const BITS: u32 = u128::BITS;
+
+    fn to_u128(self) -> u128 {
+        self.to_bits()
+    }
+
+    fn from_u128(bits: u128) -> Self {
+        Ipv6Addr::from_bits(bits)
     }
 }
 
+mod private {
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    /// Prevents `IpAddrBits` from being implemented outside of this crate.
+    pub trait Sealed {}
+
+    impl Sealed for Ipv4Addr {}
+    impl Sealed for Ipv6Addr {}
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    /// The error returned when attemping to perform clean-only operations on a dirty `IpAddrMap`.
-    #[error("tried to perform clean-only operation on IpAddrMap")]
-    DirtyIpAddrMap,
-
     /// The error returned when no value is found for a given key.
     ///
     /// Intended for use with looking up `Country`s from IP addresses.
@@ -275,4 +2293,28 @@ pub enum Error {
     /// The error returned when attemping to construct an invalid range.
     #[error("tried to construct invalid range")]
     EmptyRangeError,
+
+    /// The error returned when a country code is malformed or not recognized.
+    #[error("unrecognized country code '{0}'")]
+    InvalidCode(Box<str>),
+
+    /// The error returned when most rows of a database can't be parsed.
+    ///
+    /// Most likely, an IPv6 database was given to an IPv4 parser or vice versa.
+    #[error("{failed} of {total} rows could not be parsed, the database may be for the wrong IP address family")]
+    WrongDatabaseFormat { failed: usize, total: usize },
+
+    /// The error returned when searching an `AnyIpAddrMap` for an address of the other IP address
+    /// family.
+    #[error("IP address {0} is not in the same address family as the map")]
+    AddressFamilyMismatch(IpAddr),
+
+    /// The error returned when a map holds overlapping ranges, but `OverlapPolicy::Error` was
+    /// given.
+    #[error("the map holds overlapping ranges")]
+    OverlappingRanges,
+
+    /// The error returned when a line of a database can't be parsed into an entry.
+    #[error("malformed entry '{0}'")]
+    MalformedEntry(Box<str>),
 }
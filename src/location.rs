@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! Locations more precise than a country, for databases that also hold the subdivision (ex. state
+//! or province) and city of each range.
+
+use serde::Serialize;
+
+use crate::country_list::Country;
+
+/// A country, and optionally the subdivision and city within it.
+///
+/// Serializes as the country's fields, alongside `subdivision` and `city`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::{country_list::Country, location::Location};
+///
+/// let location = Location {
+///     country: "BE".parse::<Country>().unwrap(),
+///     subdivision: Some("BRU".into()),
+///     city: Some("Brussels".into()),
+/// };
+///
+/// assert_eq!(location.country.code, "BE".into());
+/// assert_eq!(Location::from(location.country.clone()).city, None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Location {
+    /// The country of the location.
+    #[serde(flatten)]
+    pub country: Country,
+    /// The subdivision of the country, ex. an ISO 3166-2 code like `"BRU"`, if known.
+    pub subdivision: Option<Box<str>>,
+    /// The name of the city, if known.
+    pub city: Option<Box<str>>,
+}
+
+impl From<Country> for Location {
+    /// A location known only to be within `country`.
+    fn from(country: Country) -> Self {
+        Self {
+            country,
+            subdivision: None,
+            city: None,
+        }
+    }
+}
+
+/// The zero-based positions of the columns of a city database holding the subdivision and city of
+/// each range, alongside the columns of `ColumnMap`.
+///
+/// If a column is missing or empty, that part of the location is unknown.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::location::LocationColumns;
+///
+/// assert_eq!(
+///     LocationColumns::default(),
+///     LocationColumns {
+///         subdivision: 3,
+///         city: 4,
+///     }
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocationColumns {
+    /// The column holding the subdivision of each range.
+    pub subdivision: usize,
+    /// The column holding the city of each range.
+    pub city: usize,
+}
+
+impl Default for LocationColumns {
+    fn default() -> Self {
+        Self {
+            subdivision: 3,
+            city: 4,
+        }
+    }
+}
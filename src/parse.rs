@@ -0,0 +1,685 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use csv::{ReaderBuilder, StringRecord, Trim};
+use serde::Deserialize;
+
+use crate::{
+    annotated::parse_rir_delegated_annotated,
+    country_list::{get_countries, Country},
+    Error, IpAddrMap,
+};
+
+/// Legacy or alternative country codes found in some databases, and the ISO 3166-1 alpha-2 codes
+/// they refer to.
+pub const COUNTRY_CODE_ALIASES: [(&str, &str); 2] = [
+    // United Kingdom
+    ("UK", "GB"),
+    // Greece
+    ("EL", "GR"),
+];
+
+/// The paths of Tor's IPv4 and IPv6 databases on most Linux distributions, used by
+/// `discover_database_paths()` if no database is found elsewhere.
+pub const DEFAULT_DATABASE_PATHS: (&str, &str) = ("/usr/share/tor/geoip", "/usr/share/tor/geoip6");
+
+/// Find the IPv4 and IPv6 databases, checking the well-known locations of Tor's `geoip` and
+/// `geoip6` files for the current OS in order of priority, and returning the first of each that
+/// exists.
+///
+/// Checks `/usr/share/tor/` and `/usr/local/share/tor/` on Unix, Homebrew and Tor Browser on macOS,
+/// and Tor Browser on the desktop on Windows. Falls back to `DEFAULT_DATABASE_PATHS` for either
+/// database that is not found, so the result may not exist.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::parse::discover_database_paths;
+///
+/// let (ipv4_path, ipv6_path) = discover_database_paths();
+///
+/// assert!(ipv4_path.ends_with("geoip"));
+/// assert!(ipv6_path.ends_with("geoip6"));
+/// ```
+pub fn discover_database_paths() -> (Box<Path>, Box<Path>) {
+    let well_known: &[&str] = if cfg!(target_os = "macos") {
+        &[
+            "/opt/homebrew/share/tor",
+            "/usr/local/share/tor",
+            "/Applications/Tor Browser.app/Contents/Resources/TorBrowser/Tor",
+        ]
+    } else if cfg!(unix) {
+        &["/usr/share/tor", "/usr/local/share/tor"]
+    } else {
+        &[]
+    };
+
+    let mut directories: Vec<PathBuf> = well_known.iter().map(PathBuf::from).collect();
+
+    if cfg!(windows) {
+        if let Some(profile) = env::var_os("USERPROFILE") {
+            directories.push(
+                PathBuf::from(profile).join(r"Desktop\Tor Browser\Browser\TorBrowser\Data\Tor"),
+            );
+        }
+    }
+
+    let discover = |name: &str, default: &str| -> Box<Path> {
+        directories
+            .iter()
+            .map(|directory| directory.join(name))
+            .find(|path| path.is_file())
+            .map_or_else(|| Path::new(default).into(), PathBuf::into_boxed_path)
+    };
+
+    (
+        discover("geoip", DEFAULT_DATABASE_PATHS.0),
+        discover("geoip6", DEFAULT_DATABASE_PATHS.1),
+    )
+}
+
+/// Options controlling how an IP geolocation database file is parsed.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::parse::ParseOptions;
+///
+/// let options = ParseOptions {
+///     comment: Some(b'#'),
+///     ..Default::default()
+/// };
+///
+/// assert!(options.skip_blank_lines);
+/// assert_eq!(options.delimiter, b',');
+///
+/// // A tab-separated database
+/// let options = ParseOptions {
+///     delimiter: b'\t',
+///     ..Default::default()
+/// };
+///
+/// assert!(options.quoting);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Lines starting with this byte are ignored.
+    ///
+    /// Ex. `Some(b'#')`.
+    pub comment: Option<u8>,
+    /// Whether to trim leading and trailing whitespace from each field.
+    pub trim_whitespace: bool,
+    /// Whether to skip lines that are empty or consist only of whitespace.
+    pub skip_blank_lines: bool,
+    /// Whether to keep ranges with the `"??"` placeholder country code, rather than dropping them.
+    ///
+    /// If kept, searching an address in such a range finds the `"??"` (unknown) country, instead of
+    /// no country at all.
+    pub keep_unknown: bool,
+    /// Whether to replace the warning logged for each row with an unrecognized country code with a
+    /// single warning counting them, once parsing is done.
+    pub quiet: bool,
+    /// Whether the first row is a header (ex. `network,country_code`) to skip, rather than data.
+    pub has_headers: bool,
+    /// The positions of the columns holding each field.
+    pub columns: ColumnMap,
+    /// The byte separating the fields of each row.
+    ///
+    /// Ex. `b','` for CSV, `b'\t'` for TSV, or `b';'`.
+    pub delimiter: u8,
+    /// Whether fields may be quoted (ex. `"BE"`), with delimiters inside quotes treated as part of
+    /// the field. If not, quote characters are read as part of the field.
+    pub quoting: bool,
+}
+
+impl ParseOptions {
+    /// Create a CSV reader builder configured according to `self`.
+    pub(crate) fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .has_headers(self.has_headers)
+            .comment(self.comment)
+            .delimiter(self.delimiter)
+            .quoting(self.quoting);
+
+        if self.trim_whitespace {
+            builder.trim(Trim::All);
+        }
+
+        builder
+    }
+
+    /// Returns true if `record` should be skipped as a blank line.
+    pub(crate) fn is_skipped(&self, record: &StringRecord) -> bool {
+        self.skip_blank_lines && record.iter().all(|field| field.trim().is_empty())
+    }
+
+    /// Log a warning for a row with an unrecognized country code, unless `quiet` is set.
+    pub(crate) fn warn_unknown_code(&self, code: &str) {
+        if !self.quiet {
+            log::warn!("Unrecognized country or region '{code}'!");
+        }
+    }
+
+    /// If `quiet` is set, log a single warning counting the rows of `report` with unrecognized
+    /// country codes, in place of the warnings for each row.
+    pub(crate) fn warn_unknown_codes(&self, report: &ParseReport) {
+        if self.quiet && report.skipped_unknown_code != 0 {
+            log::warn!(
+                "Skipped {} rows with unrecognized country codes",
+                report.skipped_unknown_code
+            );
+        }
+    }
+
+    /// Log a warning if `record`, the first row of a database, looks like a header but
+    /// `has_headers` is not set.
+    ///
+    /// A row looks like a header if its start column holds anything other than hexadecimal
+    /// digits, `.`, and `:`, which can't be an IP address.
+    pub(crate) fn warn_if_header(&self, record: &StringRecord) {
+        let first_column = self.columns.network.unwrap_or(self.columns.start);
+        let is_header_like = record.get(first_column).is_some_and(|start| {
+            start
+                .trim()
+                .chars()
+                .any(|c| !(c.is_ascii_hexdigit() || matches!(c, '.' | ':' | '/')))
+        });
+
+        if !self.has_headers && is_header_like {
+            log::warn!("The first row looks like a header, consider setting `has_headers`");
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            comment: None,
+            trim_whitespace: false,
+            skip_blank_lines: true,
+            keep_unknown: false,
+            quiet: false,
+            has_headers: false,
+            columns: ColumnMap::default(),
+            delimiter: b',',
+            quoting: true,
+        }
+    }
+}
+
+/// The zero-based positions of the columns of a database holding the start of each IP address
+/// range, the end, and the country code.
+///
+/// If `network` is set, each range is instead read from a single column in CIDR notation (ex.
+/// `2001:db8::/32`), and `start` and `end` are ignored. Only the IPv6 parsers support this: the
+/// IPv4 parsers find every row malformed.
+///
+/// Other columns are ignored.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::net::Ipv6Addr;
+/// use ip_geo::{
+///     ipv6::parse_ipv6_bytes,
+///     parse::{ColumnMap, ParseOptions},
+/// };
+///
+/// // For a database in the format `country_code,start,end`
+/// let options = ParseOptions {
+///     columns: ColumnMap {
+///         start: 1,
+///         end: 2,
+///         code: 0,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// // For an IPv6 database in the format `network,country_code`
+/// let options = ParseOptions {
+///     columns: ColumnMap {
+///         network: Some(0),
+///         code: 1,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// let (mut ipv6_map, _) = parse_ipv6_bytes(b"2001:db8::/32,BE\n::1/128,CA\n", options).unwrap();
+/// assert_eq!(ipv6_map.search("2001:db8:ffff::1".parse().unwrap()).unwrap().code, "BE".into());
+/// assert_eq!(ipv6_map.search(Ipv6Addr::LOCALHOST).unwrap().code, "CA".into());
+/// assert!(ipv6_map.search("::2".parse().unwrap()).is_err());
+///
+/// assert_eq!(
+///     ColumnMap::default(),
+///     ColumnMap { start: 0, end: 1, code: 2, network: None },
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColumnMap {
+    /// The column holding the first IP address of each range.
+    pub start: usize,
+    /// The column holding the last IP address of each range.
+    pub end: usize,
+    /// The column holding the country code of each range.
+    pub code: usize,
+    /// The column holding each range as a network in CIDR notation, if not `start` and `end`.
+    pub network: Option<usize>,
+}
+
+impl ColumnMap {
+    /// Select the fields of `record` in the order `start,end,code`, or `network,code` if `network`
+    /// is set.
+    ///
+    /// If any column is missing, the returned record has too few fields, and will fail to
+    /// deserialize.
+    pub(crate) fn select(&self, record: &StringRecord) -> StringRecord {
+        let columns = match self.network {
+            Some(network) => [Some(network), None, Some(self.code)],
+            None => [Some(self.start), Some(self.end), Some(self.code)],
+        };
+        let mut selected: StringRecord = columns
+            .into_iter()
+            .flatten()
+            .filter_map(|column| record.get(column))
+            .collect();
+        selected.set_position(record.position().cloned());
+
+        selected
+    }
+}
+
+impl Default for ColumnMap {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            end: 1,
+            code: 2,
+            network: None,
+        }
+    }
+}
+
+/// A summary of the rows of a database parsed into an `IpAddrMap`.
+///
+/// Example usage:
+///
+/// ```rust
+/// use ip_geo::parse::ParseReport;
+///
+/// let report = ParseReport {
+///     total_rows: 200_000,
+///     inserted: 198_231,
+///     skipped_unknown_code: 1_700,
+///     skipped_placeholder: 69,
+///     skipped_malformed: 0,
+/// };
+///
+/// assert_eq!(report.skipped(), 1_769);
+/// assert_eq!(
+///     report.to_string(),
+///     "loaded 198,231 ranges, skipped 1,769 (unknown: 1,700, placeholder: 69, malformed: 0)"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    /// The number of rows read, excluding comments and skipped blank lines.
+    pub total_rows: usize,
+    /// The number of rows inserted into the map.
+    pub inserted: usize,
+    /// The number of rows skipped because their country code was not recognized.
+    pub skipped_unknown_code: usize,
+    /// The number of rows skipped because their country code was the `"??"` placeholder.
+    pub skipped_placeholder: usize,
+    /// The number of rows skipped because they could not be parsed.
+    pub skipped_malformed: usize,
+}
+
+impl ParseReport {
+    /// Return the total number of rows that were not inserted into the map.
+    pub fn skipped(&self) -> usize {
+        self.skipped_unknown_code + self.skipped_placeholder + self.skipped_malformed
+    }
+}
+
+impl Display for ParseReport {
+    /// Format as a one line summary, ex. `loaded 198,231 ranges, skipped 1,769 (unknown: 1,700,
+    /// placeholder: 69, malformed: 0)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Format an integer with commas separating groups of three digits.
+        fn group_digits(number: usize) -> String {
+            let digits = number.to_string();
+            let mut output = String::with_capacity(digits.len() + digits.len() / 3);
+
+            for (index, digit) in digits.chars().enumerate() {
+                if index != 0 && (digits.len() - index) % 3 == 0 {
+                    output.push(',');
+                }
+
+                output.push(digit);
+            }
+
+            output
+        }
+
+        write!(
+            f,
+            "loaded {} ranges, skipped {} (unknown: {}, placeholder: {}, malformed: {})",
+            group_digits(self.inserted),
+            group_digits(self.skipped()),
+            group_digits(self.skipped_unknown_code),
+            group_digits(self.skipped_placeholder),
+            group_digits(self.skipped_malformed),
+        )
+    }
+}
+
+/// The length in bytes of the shortest possible row of a database, ex. `0,0,BE\n`.
+const MIN_ROW_LEN: u64 = 7;
+
+/// Return the capacity to parse a database `file` of an expected `len` rows into: `len`, or fewer
+/// if the file is too small to hold that many rows.
+pub(crate) fn initial_capacity(file: &fs::File, len: usize) -> usize {
+    match file.metadata() {
+        Ok(metadata) => {
+            len.min(usize::try_from(metadata.len() / MIN_ROW_LEN).unwrap_or(usize::MAX))
+        }
+        Err(_) => len,
+    }
+}
+
+/// Return the capacity to parse an in-memory database into: the number of lines in `data`.
+pub(crate) fn count_rows(data: &[u8]) -> usize {
+    data.split(|&byte| byte == b'\n').count()
+}
+
+/// Log a warning if a database parsed into no ranges, as every search of it would find nothing.
+///
+/// `path` is `None` for an in-memory database.
+pub(crate) fn warn_if_empty(path: Option<&Path>, report: &ParseReport) {
+    if report.inserted != 0 {
+        return;
+    }
+
+    match path {
+        Some(path) => log::warn!(
+            "The database at {} holds no ranges, so no IP address will be found",
+            path.display()
+        ),
+        None => {
+            log::warn!("The in-memory database holds no ranges, so no IP address will be found")
+        }
+    }
+}
+
+/// How far, as a fraction of the ranges inserted, the expected length of a database may be off
+/// before `warn_if_len_mismatch` suggests a better one.
+const LEN_MARGIN_DIVISOR: usize = 10;
+
+/// Log a single warning if the expected `len` of the database at `path` is off by more than a
+/// tenth of the ranges actually inserted, suggesting the correct length.
+///
+/// Too small a length reallocates the map while parsing, and too large a length wastes memory.
+pub(crate) fn warn_if_len_mismatch(path: &Path, len: usize, report: &ParseReport) {
+    let inserted = report.inserted;
+
+    if inserted == 0 || len.abs_diff(inserted) <= inserted / LEN_MARGIN_DIVISOR {
+        return;
+    }
+
+    log::warn!(
+        "The database at {} was expected to hold {len} ranges, but holds {inserted}; \
+         set its length to {inserted} to size the map correctly",
+        path.display()
+    );
+}
+
+/// For a given country code from a database, find the matching country in `countries`.
+///
+/// Matches case-insensitively, then falls back to `COUNTRY_CODE_ALIASES`.
+pub(crate) fn find_country(countries: &HashMap<Arc<str>, Country>, code: &str) -> Option<Country> {
+    let code = code.to_uppercase();
+
+    if let Some(country) = countries.get(code.as_str()) {
+        return Some(country.clone());
+    }
+
+    let (alias, canonical) = COUNTRY_CODE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == code)?;
+
+    log::info!("Treating country code '{alias}' as '{canonical}'");
+    countries.get(*canonical).cloned()
+}
+
+/// Tracks the rows of a database that could not be parsed.
+///
+/// If most rows can't be parsed, the database is most likely for the wrong IP address family.
+#[derive(Default)]
+pub(crate) struct RowErrors {
+    total: usize,
+    failed: usize,
+    first: Option<csv::Error>,
+}
+
+impl RowErrors {
+    /// Record the result of parsing a row, returning the parsed row if it succeeded.
+    pub(crate) fn record<R>(&mut self, row: Result<R, csv::Error>) -> Option<R> {
+        self.total += 1;
+
+        match row {
+            Ok(row) => Some(row),
+            Err(error) => {
+                self.failed += 1;
+                self.first.get_or_insert(error);
+
+                None
+            }
+        }
+    }
+
+    /// Returns `Error::WrongDatabaseFormat` if more than half of the recorded rows failed to parse.
+    /// Otherwise, logs a warning if any rows failed.
+    pub(crate) fn check(self) -> Result<(), Error> {
+        let Self {
+            total,
+            failed,
+            first,
+        } = self;
+
+        if failed * 2 > total {
+            return Err(Error::WrongDatabaseFormat { failed, total });
+        }
+
+        if let Some(error) = first {
+            log::warn!(
+                "Skipped {failed} of {total} rows that could not be parsed, first error: {error}"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// An `IpAddrMap` holding IPv4 addresses and one holding IPv6 addresses, as parsed from a database
+/// that mixes both families, with a `Country` (or ex. an `Annotated<Country>`) for each range.
+pub type CountryMaps<C = Country> = (IpAddrMap<Ipv4Addr, C>, IpAddrMap<Ipv6Addr, C>);
+
+/// Parse a [DB-IP](https://db-ip.com) lite country database (ex. `dbip-country-lite.csv`) into
+/// `IpAddrMap`s holding IPv4 and IPv6 addresses.
+///
+/// DB-IP mixes both IP address families in one file, in the format `start_ip,end_ip,country_code`,
+/// with IP addresses as strings (ex. `1.0.0.0` or `2001:200::`). Each row is inserted into the
+/// map of the family of its start address.
+///
+/// Rows that can't be parsed, including those whose start and end are of different families, are
+/// skipped and logged as a warning. If more than half of the rows can't be parsed, returns
+/// `Error::WrongDatabaseFormat`.
+///
+/// # Panics
+///
+/// Panics if the file at `path` can't be opened.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{io::Write, net::{Ipv4Addr, Ipv6Addr}, str::FromStr};
+///
+/// let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+/// write!(temp_file, "1.0.0.0,1.0.0.255,BE\n2001:200::,2001:200::ffff,CA\n").unwrap();
+///
+/// let (ipv4_map, ipv6_map) = ip_geo::parse::parse_dbip_lite(temp_file.path()).unwrap();
+///
+/// assert_eq!(ipv4_map.try_search(Ipv4Addr::new(1, 0, 0, 1)).unwrap().code, "BE".into());
+/// assert_eq!(
+///     ipv6_map.try_search(Ipv6Addr::from_str("2001:200::1").unwrap()).unwrap().code,
+///     "CA".into()
+/// );
+/// ```
+pub fn parse_dbip_lite(path: &Path) -> Result<CountryMaps, Error> {
+    #[derive(Deserialize, Debug)]
+    struct Schema {
+        start: IpAddr,
+        end: IpAddr,
+        country_code: Box<str>,
+    }
+
+    let file = fs::File::open(path).unwrap_or_else(|_| {
+        panic!(
+            "Could not open DB-IP database at {}",
+            path.to_string_lossy()
+        )
+    });
+    let options = ParseOptions {
+        trim_whitespace: true,
+        ..Default::default()
+    };
+    let mut reader = options.reader_builder().from_reader(file);
+
+    let mut ipv4_map = IpAddrMap::new();
+    let mut ipv6_map = IpAddrMap::new();
+    let countries = get_countries();
+    let mut row_errors = RowErrors::default();
+    let mut report = ParseReport::default();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) if options.is_skipped(&record) => continue,
+            record => record,
+        };
+
+        report.total_rows += 1;
+
+        let data = record.and_then(|record| record.deserialize::<Schema>(None));
+        let Some(data) = row_errors.record(data) else {
+            report.skipped_malformed += 1;
+            continue;
+        };
+
+        let Some(country) = find_country(&countries, &data.country_code) else {
+            options.warn_unknown_code(&data.country_code);
+            report.skipped_unknown_code += 1;
+            continue;
+        };
+
+        // Only add ranges with associated countries
+        if country.code == "??".into() {
+            report.skipped_placeholder += 1;
+            continue;
+        }
+
+        match (data.start, data.end) {
+            (IpAddr::V4(start), IpAddr::V4(end)) => ipv4_map.insert_range(start, end, country)?,
+            (IpAddr::V6(start), IpAddr::V6(end)) => ipv6_map.insert_range(start, end, country)?,
+            (start, end) => {
+                log::warn!("Skipped range {start}–{end} of mixed IP address families");
+                report.skipped_malformed += 1;
+                continue;
+            }
+        }
+
+        report.inserted += 1;
+    }
+
+    row_errors.check()?;
+    options.warn_unknown_codes(&report);
+    log::info!("DB-IP database: {report}");
+
+    ipv4_map.cleanup();
+    ipv6_map.cleanup();
+
+    Ok((ipv4_map, ipv6_map))
+}
+
+/// Parse a Regional Internet Registry delegation file (ex. `delegated-ripencc-extended-latest`)
+/// into `IpAddrMap`s holding IPv4 and IPv6 addresses.
+///
+/// Delegation files are pipe-delimited, in the format `registry|cc|type|start|value|date|status`,
+/// optionally followed by an opaque ID in the extended format. IPv4 records give the number of
+/// addresses in each range as `value`, and IPv6 records give the prefix length, which are
+/// converted to the end of each range. Records of autonomous system numbers (`asn`) are ignored.
+///
+/// See `annotated::parse_rir_delegated_annotated()` for the details, and to keep the registry and
+/// registration date of each range.
+///
+/// # Panics
+///
+/// Panics if the file at `path` can't be opened.
+///
+/// Example usage:
+///
+/// ```rust
+/// use std::{io::Write, net::{Ipv4Addr, Ipv6Addr}, str::FromStr};
+///
+/// let mut temp_file = tempfile::NamedTempFile::new().unwrap();
+/// write!(
+///     temp_file,
+///     "arin|US|ipv4|3.0.0.0|16777216|19880223|allocated|e5e3b9c13678dfc483fb1f819d70883c\n\
+///      arin|US|asn|1|1|19910301|assigned|5b6c41f4f4c2d6ba0c4b7f9f87bb1bb0\n\
+///      arin|CA|ipv6|2001:400::|23|19990803|allocated|bdfb2c5fe5a9d1ae9d8c1b1f3ef51ed8\n"
+/// )
+/// .unwrap();
+///
+/// let (ipv4_map, ipv6_map) = ip_geo::parse::parse_rir_delegated(temp_file.path()).unwrap();
+///
+/// assert_eq!(ipv4_map.try_search(Ipv4Addr::new(3, 255, 255, 255)).unwrap().code, "US".into());
+/// assert!(ipv4_map.try_search(Ipv4Addr::new(4, 0, 0, 0)).is_err());
+/// assert_eq!(
+///     ipv6_map.try_search(Ipv6Addr::from_str("2001:5ff::1").unwrap()).unwrap().code,
+///     "CA".into()
+/// );
+/// ```
+pub fn parse_rir_delegated(path: &Path) -> Result<CountryMaps, Error> {
+    let (ipv4_map, ipv6_map) = parse_rir_delegated_annotated(path)?;
+
+    Ok((
+        ipv4_map.map_values(|annotated| annotated.data),
+        ipv6_map.map_values(|annotated| annotated.data),
+    ))
+}
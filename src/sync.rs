@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! The interior mutability used to lazily clean an `IpAddrMap` through a shared reference.
+//!
+//! With the `std` feature, these are thread-safe. Without it, they are the single-threaded
+//! `core::cell` equivalents, so an `IpAddrMap` is not `Sync`.
+
+use core::ops::DerefMut;
+
+#[cfg(not(feature = "std"))]
+pub(crate) use core::cell::OnceCell as OnceLock;
+#[cfg(feature = "std")]
+pub(crate) use std::sync::OnceLock;
+
+#[cfg(feature = "std")]
+type Inner<T> = std::sync::Mutex<T>;
+#[cfg(not(feature = "std"))]
+type Inner<T> = core::cell::RefCell<T>;
+
+/// A `std::sync::Mutex` that ignores poisoning, or a `RefCell` without the `std` feature.
+///
+/// Poisoning is ignored because the protected data is only ever moved out whole, so a panic can't
+/// leave it half-modified.
+#[derive(Debug, Default)]
+pub(crate) struct Mutex<T>(Inner<T>);
+
+impl<T> Mutex<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self(Inner::new(value))
+    }
+
+    /// Lock the mutex, blocking until it is available.
+    ///
+    /// Without the `std` feature, panics if it is already locked.
+    pub(crate) fn lock(&self) -> impl DerefMut<Target = T> + '_ {
+        #[cfg(feature = "std")]
+        return self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        #[cfg(not(feature = "std"))]
+        return self.0.borrow_mut();
+    }
+
+    /// Return a mutable reference to the underlying data, without locking.
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        #[cfg(feature = "std")]
+        return self
+            .0
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        #[cfg(not(feature = "std"))]
+        return self.0.get_mut();
+    }
+}
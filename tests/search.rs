@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//
+// Copyright © 2024 RemasteredArch
+//
+// This file is part of ip_geo.
+//
+// ip_geo is free software: you can redistribute it and/or modify it under the terms of the GNU
+// Affero General Public License as published by the Free Software Foundation, either version 3 of
+// the License, or (at your option) any later version.
+//
+// ip_geo is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without
+// even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License along with ip_geo. If
+// not, see <https://www.gnu.org/licenses/>.
+
+//! Tests of `IpAddrMap` search correctness, mostly randomized.
+
+use std::{
+    cmp::Reverse,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use ip_geo::{Error, IpAddrBits, IpAddrEntry, IpAddrMap, OverlapPolicy};
+use proptest::prelude::*;
+
+/// Generate up to 64 ranges as `(start, end)` pairs, in ascending order, that do not overlap and
+/// have a gap of at least one address between each other.
+fn disjoint_ranges() -> impl Strategy<Value = Vec<(u32, u32)>> {
+    prop::collection::vec((1..1_000u32, 0..1_000u32), 0..64).prop_map(|pairs| {
+        let mut next = 0;
+
+        pairs
+            .into_iter()
+            .map(|(gap, len)| {
+                let start = next + gap;
+                let end = start + len;
+                next = end + 1;
+
+                (start, end)
+            })
+            .collect()
+    })
+}
+
+/// Generate up to 64 ranges as `(start, end)` pairs, which may overlap each other.
+fn overlapping_ranges() -> impl Strategy<Value = Vec<(u32, u32)>> {
+    prop::collection::vec((0..2_000u32, 0..500u32), 0..64).prop_map(|pairs| {
+        pairs
+            .into_iter()
+            .map(|(start, len)| (start, start + len))
+            .collect()
+    })
+}
+
+/// Build a map from `ranges`, in the given order, where the value of each range is its index.
+fn build_map(ranges: &[(u32, u32)], order: &[usize]) -> IpAddrMap<Ipv4Addr, usize> {
+    let mut map = IpAddrMap::new();
+
+    for &index in order {
+        let (start, end) = ranges[index];
+        map.insert_range(Ipv4Addr::from_bits(start), Ipv4Addr::from_bits(end), index)
+            .unwrap();
+    }
+
+    map
+}
+
+/// Generate ranges alongside a random order to insert them in.
+fn with_order(
+    ranges: impl Strategy<Value = Vec<(u32, u32)>>,
+) -> impl Strategy<Value = (Vec<(u32, u32)>, Vec<usize>)> {
+    ranges.prop_flat_map(|ranges| {
+        let order = Just((0..ranges.len()).collect::<Vec<_>>()).prop_shuffle();
+
+        (Just(ranges), order)
+    })
+}
+
+proptest! {
+    /// Every address in a range resolves to that range's value, and every address in a gap between
+    /// ranges returns `Error::NoValueFound`, regardless of insertion order.
+    #[test]
+    fn disjoint_ranges_resolve((ranges, order) in with_order(disjoint_ranges())) {
+        let map = build_map(&ranges, &order);
+
+        for (index, &(start, end)) in ranges.iter().enumerate() {
+            for address in [start, start + (end - start) / 2, end] {
+                prop_assert_eq!(map.try_search(Ipv4Addr::from_bits(address)).ok(), Some(&index));
+            }
+
+            for address in [start - 1, end + 1] {
+                prop_assert!(matches!(
+                    map.try_search(Ipv4Addr::from_bits(address)),
+                    Err(Error::NoValueFound)
+                ));
+            }
+        }
+    }
+
+    /// With overlapping ranges, which range is found is unspecified, and an address may not be
+    /// found at all. Any value that is found must still be from a range containing the address.
+    #[test]
+    fn overlapping_ranges_find_a_containing_range(
+        (ranges, order) in with_order(overlapping_ranges()),
+        addresses in prop::collection::vec(0..2_500u32, 1..64),
+    ) {
+        let map = build_map(&ranges, &order);
+
+        for address in addresses {
+            match map.try_search(Ipv4Addr::from_bits(address)) {
+                Ok(&index) => {
+                    let (start, end) = ranges[index];
+                    prop_assert!((start..=end).contains(&address));
+                }
+                Err(error) => prop_assert!(matches!(error, Error::NoValueFound)),
+            }
+        }
+    }
+
+    /// `search_all` returns exactly the ranges containing an address, even where ranges overlap.
+    #[test]
+    fn search_all_returns_every_containing_range(
+        (ranges, order) in with_order(overlapping_ranges()),
+        address in 0..2_600u32,
+    ) {
+        let map = build_map(&ranges, &order);
+
+        let mut found: Vec<usize> = map
+            .search_all(Ipv4Addr::from_bits(address))
+            .into_iter()
+            .map(|entry| *entry.value())
+            .collect();
+        found.sort_unstable();
+
+        let mut expected: Vec<usize> = ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, (start, end))| (start..=end).contains(&&address))
+            .map(|(index, _)| index)
+            .collect();
+        expected.sort_unstable();
+
+        prop_assert_eq!(found, expected);
+    }
+
+    /// An `IntervalMap` finds the same containing ranges as `IpAddrMap::search_all`, and searches
+    /// resolve to the one that starts last, then ends first.
+    #[test]
+    fn interval_map_matches_search_all(
+        (ranges, order) in with_order(overlapping_ranges()),
+        address in 0..2_600u32,
+    ) {
+        let map = build_map(&ranges, &order);
+        let address = Ipv4Addr::from_bits(address);
+        let values = |entries: Vec<&IpAddrEntry<Ipv4Addr, usize>>| -> Vec<usize> {
+            entries.into_iter().map(|entry| *entry.value()).collect()
+        };
+
+        let expected = values(map.search_all(address));
+        let interval_map = map.into_interval_map();
+        let found = values(interval_map.search_all(address));
+        prop_assert_eq!(&found, &expected);
+
+        let most_specific = expected
+            .iter()
+            .map(|&index| ranges[index])
+            .max_by_key(|&(start, end)| (start, Reverse(end)));
+        let searched = interval_map.search(address).ok().map(|&index| ranges[index]);
+        prop_assert_eq!(searched, most_specific);
+    }
+
+    /// After `cleanup_with`, every address resolves to the entry the policy chose for it, and no
+    /// two ranges overlap.
+    #[test]
+    fn cleanup_with_resolves_overlaps_by_policy(
+        (ranges, order) in with_order(overlapping_ranges()),
+        policy in prop::sample::select(vec![
+            OverlapPolicy::KeepFirst,
+            OverlapPolicy::KeepLast,
+            OverlapPolicy::KeepMoreSpecific,
+        ]),
+    ) {
+        let mut map = build_map(&ranges, &order);
+        map.cleanup_with(policy).unwrap();
+
+        for pair in map.iter().collect::<Vec<_>>().windows(2) {
+            prop_assert!(pair[0].end() < pair[1].start());
+        }
+
+        for address in 0..2_600u32 {
+            // The indices of the ranges containing `address`, in insertion order
+            let containing: Vec<usize> = order
+                .iter()
+                .copied()
+                .filter(|&index| (ranges[index].0..=ranges[index].1).contains(&address))
+                .collect();
+
+            let expected = match policy {
+                OverlapPolicy::KeepFirst => containing.first().copied(),
+                OverlapPolicy::KeepLast => containing.last().copied(),
+                _ => containing
+                    .iter()
+                    .copied()
+                    .min_by_key(|&index| ranges[index].1 - ranges[index].0),
+            };
+
+            prop_assert_eq!(map.try_search(Ipv4Addr::from_bits(address)).ok().copied(), expected);
+        }
+    }
+
+    /// `OverlapPolicy::Error` fails if and only if two ranges overlap.
+    #[test]
+    fn cleanup_with_error_detects_overlaps((ranges, order) in with_order(overlapping_ranges())) {
+        let mut map = build_map(&ranges, &order);
+
+        let overlapping = ranges.iter().enumerate().any(|(index, (start, end))| {
+            ranges[index + 1..]
+                .iter()
+                .any(|(other_start, other_end)| start <= other_end && other_start <= end)
+        });
+
+        prop_assert_eq!(map.cleanup_with(OverlapPolicy::Error).is_err(), overlapping);
+        prop_assert_eq!(map.len(), ranges.len());
+    }
+
+    /// Within the universe, every address is either in a range of the map or in exactly one range
+    /// of its complement, and the ranges of the complement are in order and don't touch.
+    #[test]
+    fn complement_covers_the_rest_of_the_universe(
+        (ranges, order) in with_order(overlapping_ranges()),
+        (universe_start, universe_end) in (0..2_500u32, 0..2_500u32),
+    ) {
+        let map = build_map(&ranges, &order);
+        let gaps: Vec<(u32, u32)> = map
+            .complement(Ipv4Addr::from_bits(universe_start), Ipv4Addr::from_bits(universe_end))
+            .into_iter()
+            .map(|(start, end)| (start.to_bits(), end.to_bits()))
+            .collect();
+
+        for pair in gaps.windows(2) {
+            prop_assert!(pair[0].1 + 1 < pair[1].0);
+        }
+
+        for address in universe_start..=universe_end {
+            let in_map = ranges.iter().any(|(start, end)| (start..=end).contains(&&address));
+            let in_gaps = gaps
+                .iter()
+                .filter(|(start, end)| (start..=end).contains(&&address))
+                .count();
+
+            prop_assert_eq!(in_gaps, usize::from(!in_map));
+        }
+
+        if universe_start > universe_end {
+            prop_assert!(gaps.is_empty());
+        }
+    }
+
+    /// `prev_addr` undoes `next_addr`, except at the end of the address space.
+    #[test]
+    fn next_addr_and_prev_addr_round_trip(bits in any::<u32>()) {
+        let address = Ipv4Addr::from_bits(bits);
+
+        match address.next_addr() {
+            Some(next) => prop_assert_eq!(next.prev_addr(), Some(address)),
+            None => prop_assert_eq!(address, Ipv4Addr::BROADCAST),
+        }
+    }
+}
+
+/// Ranges touching either edge of the address space can be searched, counted, and split into CIDR
+/// blocks without overflowing.
+#[test]
+fn ranges_at_address_space_edges() {
+    let mut ipv4_map = IpAddrMap::new();
+    ipv4_map
+        .insert_range(Ipv4Addr::new(255, 255, 255, 0), Ipv4Addr::BROADCAST, "last")
+        .unwrap();
+    ipv4_map
+        .insert_range(Ipv4Addr::UNSPECIFIED, Ipv4Addr::new(0, 0, 0, 255), "first")
+        .unwrap();
+
+    assert_eq!(
+        ipv4_map.try_search(Ipv4Addr::UNSPECIFIED).ok(),
+        Some(&"first")
+    );
+    assert_eq!(ipv4_map.try_search(Ipv4Addr::BROADCAST).ok(), Some(&"last"));
+
+    let first = ipv4_map.get_from_index_as_ref(0).unwrap();
+    assert_eq!(first.start().prev_addr(), None);
+
+    let last = ipv4_map.get_from_index_as_ref(1).unwrap();
+    assert_eq!(last.end().next_addr(), None);
+    assert_eq!(last.count(), 256);
+    assert_eq!(last.to_cidrs(), [(Ipv4Addr::new(255, 255, 255, 0), 24)]);
+
+    let max = Ipv6Addr::from_bits(u128::MAX);
+    let mut ipv6_map = IpAddrMap::new();
+    ipv6_map
+        .insert_range(max.prev_addr().unwrap(), max, "last")
+        .unwrap();
+
+    assert_eq!(ipv6_map.try_search(max).ok(), Some(&"last"));
+
+    let last = ipv6_map.get_from_index_as_ref(0).unwrap();
+    assert_eq!(last.end().next_addr(), None);
+    assert_eq!(last.count(), 2);
+    assert_eq!(last.to_cidrs(), [(max.prev_addr().unwrap(), 127)]);
+}